@@ -14,6 +14,9 @@ fn main() {
     let device_info = self::device_info();
     let mut device = gcast::Device::connect(device_info, &mut io).unwrap();
 
+    // Find out the real UUID and name of the device.
+    device.device_info().unwrap();
+
     /// Launch the YouTube app.
     device.launch(gcast::apps::youtube()).unwrap();
     device.set_volume(None, Some(true)).unwrap();
@@ -29,11 +32,23 @@ fn main() {
             device.handle_io(io_event).unwrap();
         }
 
+        device.update().unwrap();
+
         for event in device.events() {
             match event {
                 gcast::Event::StatusUpdated => {
                     println!("device status updated: {:?}", device.status());
                 },
+                gcast::Event::DeviceInfoUpdated => {
+                    println!("connected to {:?} ({})", device.info().name, device.info().uuid);
+                },
+                gcast::Event::StandbyChanged { is_stand_by } => {
+                    println!("TV standby: {}", is_stand_by);
+                },
+                gcast::Event::MessageReceived(message) => {
+                    println!("received unhandled message: {:?}", message);
+                },
+                _ => (),
             }
         }
     }
@@ -57,8 +72,5 @@ fn device_info() -> gcast::DeviceInfo {
         },
     };
 
-    gcast::DeviceInfo {
-        ip_addr: ip_addr,
-        uuid: "d7288042-190b-5974-aa3b-2558f1cb0c0e".parse().unwrap(),
-    }
+    gcast::DeviceInfo::from_ip_addr(ip_addr)
 }
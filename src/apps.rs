@@ -17,3 +17,7 @@ define_application!(netflix => "Netflix");
 define_application!(youtube => "YouTube");
 // The 'Chrome mirroring' functionality
 define_application!(mirroring => "0F5096E8");
+// The Default Media Receiver
+define_application!(default_media_receiver => "CC1AD845");
+// The idle screen
+define_application!(backdrop => "E8C28D3C");
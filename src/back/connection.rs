@@ -1,43 +1,131 @@
 use Error;
 use discovery;
 use back::{net, protocol};
+use back::net::transport::RawPacket;
+use recording::{Recorder, Direction};
+
+use std::collections::VecDeque;
 
 use mio;
 
 /// A connection to a Cast device.
 pub struct Connection
 {
-    pub transport: net::Transport,
+    link: Link,
+    /// Where sent and received packets are recorded, if anywhere.
+    recorder: Option<Recorder>,
+}
+
+/// What is on the other end of a connection.
+enum Link
+{
+    /// A Cast device, reached through a transport.
+    Network(net::Transport),
+    /// Nothing. Received packets are given to the connection by a
+    /// replay, and sent packets are dropped.
+    Replay(VecDeque<RawPacket>),
 }
 
 impl Connection
 {
+    /// Creates a connection that sends packets over a transport.
+    pub fn new(transport: net::Transport) -> Self {
+        Connection {
+            link: Link::Network(transport),
+            recorder: None,
+        }
+    }
+
     pub fn connect_to(device: &discovery::DeviceInfo,
                       io: &mut net::Io) -> Result<Self, Error> {
-        Ok(Connection {
-            transport: net::Transport::connect_to(device, io)?,
-        })
+        Ok(Connection::new(net::Transport::connect_to(device, io)?))
+    }
+
+    /// Creates a connection that is not connected to anything.
+    ///
+    /// Packets given to `feed` are received as if a device had sent them.
+    pub fn replay() -> Self {
+        Connection {
+            link: Link::Replay(VecDeque::new()),
+            recorder: None,
+        }
+    }
+
+    /// Gives a replayed connection a packet to receive.
+    ///
+    /// Packets fed to a network connection are ignored.
+    pub fn feed(&mut self, packet: RawPacket) {
+        if let Link::Replay(ref mut packets) = self.link {
+            packets.push_back(packet);
+        }
+    }
+
+    /// Starts recording every packet sent and received.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Stops recording.
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
     }
 
     /// Sends a packet through the connection.
     pub fn send(&mut self, message: &protocol::Message) -> Result<(), Error> {
         let bytes = message.as_raw_bytes()?;
-        self.transport.send(bytes)?;
+
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.record(Direction::Request, &bytes);
+        }
+
+        match self.link {
+            Link::Network(ref mut transport) => transport.send(bytes)?,
+            Link::Replay(..) => (),
+        }
+
         Ok(())
     }
 
     /// Consumes all packets that have been received.
-    pub fn receive(&mut self) -> Result<::std::vec::IntoIter<protocol::Message>, Error> {
-        let result: Result<Vec<protocol::Message>, Error> = self.transport.receive().map(|raw_packet| {
+    ///
+    /// Each packet is decoded on its own, so a packet that can't be decoded
+    /// gives an error in its place without losing the packets around it.
+    pub fn receive(&mut self) -> ::std::vec::IntoIter<Result<protocol::Message, Error>> {
+        let packets: Vec<RawPacket> = match self.link {
+            Link::Network(ref mut transport) => transport.receive().collect(),
+            Link::Replay(ref mut packets) => packets.drain(..).collect(),
+        };
+
+        // Packets are recorded before they are decoded, so that a packet
+        // we fail to understand can still be replayed.
+        if let Some(ref mut recorder) = self.recorder {
+            for packet in packets.iter() {
+                recorder.record(Direction::Response, packet);
+            }
+        }
+
+        let messages: Vec<Result<protocol::Message, Error>> = packets.into_iter().map(|raw_packet| {
             let message = protocol::Message::from_raw_bytes(&raw_packet)?;
             Ok(message)
         }).collect();
 
-        Ok(result?.into_iter())
+        messages.into_iter()
+    }
+
+    /// Sends and receives packets on transports that are not driven by
+    /// IO events.
+    pub fn update(&mut self) -> Result<(), Error> {
+        match self.link {
+            Link::Network(ref mut transport) => transport.update(),
+            Link::Replay(..) => Ok(()),
+        }
     }
 
     /// Handles an IO event.
     pub fn handle_event(&mut self, event: mio::Event) -> Result<(), Error> {
-        self.transport.handle_event(event)
+        match self.link {
+            Link::Network(ref mut transport) => transport.handle_event(event),
+            Link::Replay(..) => Ok(()),
+        }
     }
 }
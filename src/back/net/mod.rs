@@ -2,6 +2,9 @@
 
 pub use self::transport::Transport;
 pub use self::io::Io;
+pub use self::pipe::{pipe, Pipe};
 
 pub mod transport;
 pub mod io;
+pub mod pipe;
+pub mod tls;
@@ -0,0 +1,91 @@
+//! In-memory streams.
+
+use std::io::prelude::*;
+use std::sync::mpsc;
+use std::{cmp, io};
+
+/// One end of an in-memory stream.
+///
+/// Bytes written to one end can be read from the other. Like a
+/// non-blocking socket, reads fail with `WouldBlock` until something has
+/// been written, and reach the end of the stream once the other end has
+/// been dropped.
+pub struct Pipe
+{
+    sender: mpsc::Sender<Vec<u8>>,
+    receiver: mpsc::Receiver<Vec<u8>>,
+    /// Bytes that have been received but not yet read.
+    buffer: Vec<u8>,
+}
+
+/// Creates both ends of an in-memory stream.
+pub fn pipe() -> (Pipe, Pipe) {
+    let (a_sender, b_receiver) = mpsc::channel();
+    let (b_sender, a_receiver) = mpsc::channel();
+
+    (Pipe { sender: a_sender, receiver: a_receiver, buffer: Vec::new() },
+     Pipe { sender: b_sender, receiver: b_receiver, buffer: Vec::new() })
+}
+
+impl Read for Pipe
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffer.is_empty() {
+            match self.receiver.try_recv() {
+                Ok(bytes) => self.buffer = bytes,
+                Err(mpsc::TryRecvError::Empty) => return Err(io::ErrorKind::WouldBlock.into()),
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let count = cmp::min(buf.len(), self.buffer.len());
+        buf[..count].copy_from_slice(&self.buffer[..count]);
+        self.buffer.drain(..count);
+        Ok(count)
+    }
+}
+
+impl Write for Pipe
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing is sent for empty writes, which the other end would
+        // mistake for the end of the stream.
+        if buf.is_empty() { return Ok(0) };
+
+        self.sender.send(buf.to_owned()).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the other end of the pipe was dropped")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn reads_what_the_other_end_wrote() {
+        let (mut a, mut b) = pipe();
+        a.write_all(b"hello").unwrap();
+
+        let mut buffer = [0; 3];
+        assert_eq!(b.read(&mut buffer).unwrap(), 3);
+        assert_eq!(&buffer, b"hel");
+        assert_eq!(b.read(&mut buffer).unwrap(), 2);
+        assert_eq!(&buffer[..2], b"lo");
+        assert_eq!(b.read(&mut buffer).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn dropping_an_end_closes_the_stream() {
+        let (a, mut b) = pipe();
+        drop(a);
+
+        assert_eq!(b.read(&mut [0; 4]).unwrap(), 0);
+        assert_eq!(b.write(b"hello").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
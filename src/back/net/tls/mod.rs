@@ -0,0 +1,62 @@
+//! The TLS libraries that transports can use.
+//!
+//! Which libraries are available depends on cargo features. The `openssl`
+//! feature is enabled by default. The `rustls` feature gives a TLS
+//! library written in Rust, for builds that can't link against openssl,
+//! such as static musl builds:
+//!
+//! ```text
+//! gcast = { version = "0.1", default-features = false, features = ["rustls"] }
+//! ```
+//!
+//! Only senders can use rustls. The `receiver`, `emulator` and `proxy`
+//! modules need openssl.
+
+#[cfg(not(any(feature = "openssl", feature = "rustls")))]
+compile_error!("gcast needs a TLS library: enable the `openssl` or `rustls` feature");
+
+#[cfg(feature = "openssl")]
+pub use self::openssl::OpenSsl;
+#[cfg(feature = "rustls")]
+pub use self::rustls::Rustls;
+
+#[cfg(feature = "openssl")]
+pub mod openssl;
+#[cfg(feature = "rustls")]
+pub mod rustls;
+
+use Error;
+use back::net::transport::Stream;
+
+use std::sync::Arc;
+
+use mio;
+
+/// A TLS library that transports can talk to Cast devices with.
+///
+/// Cast devices present self-signed certificates, so backends must
+/// accept certificates that are not signed by a certificate authority.
+pub trait TlsBackend: Send + Sync
+{
+    /// Starts a TLS session over a stream that has just connected to a device.
+    ///
+    /// The stream is non-blocking, and the stream that is returned must not
+    /// block either.
+    fn connect(&self, stream: mio::tcp::TcpStream) -> Result<Box<Stream>, Error>;
+}
+
+/// Gets the backend used when none is given.
+///
+/// This is openssl if the `openssl` feature is enabled, and rustls otherwise.
+#[cfg(feature = "openssl")]
+pub fn default_backend() -> Arc<TlsBackend> {
+    Arc::new(OpenSsl)
+}
+
+/// Gets the backend used when none is given.
+///
+/// This is openssl if the `openssl` feature is enabled, and rustls otherwise.
+#[cfg(all(feature = "rustls", not(feature = "openssl")))]
+pub fn default_backend() -> Arc<TlsBackend> {
+    Arc::new(Rustls::new())
+}
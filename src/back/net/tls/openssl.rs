@@ -0,0 +1,50 @@
+//! TLS using openssl.
+
+use {Error, ErrorKind};
+use back::net::transport::Stream;
+use back::net::tls::TlsBackend;
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::io;
+
+use libc;
+use mio;
+use openssl::ssl;
+
+/// Talks to devices using openssl.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OpenSsl;
+
+impl TlsBackend for OpenSsl
+{
+    fn connect(&self, stream: mio::tcp::TcpStream) -> Result<Box<Stream>, Error> {
+        let fd = stream.as_raw_fd();
+
+        // SslStream doesn't seem to properly support non-blocking sockets.
+        // We temporarily turn off the flag until the handshake finishes.
+        set_non_blocking(fd, false)?;
+
+        let mut connector_builder = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls())?;
+        connector_builder.builder_mut().set_verify(ssl::SSL_VERIFY_NONE);
+
+        let connector = connector_builder.build();
+        let ssl_stream = connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream)
+            .map_err(|e| ErrorKind::TlsHandshake(e.to_string()))?;
+
+        set_non_blocking(fd, true)?;
+
+        Ok(Box::new(ssl_stream))
+    }
+}
+
+fn set_non_blocking(fd: RawFd, non_blocking: bool) -> Result<(), Error> {
+    let status = unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL, 0);
+        let flags = if non_blocking { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+
+        libc::fcntl(fd, libc::F_SETFL, flags)
+    };
+
+    if status == -1 { return Err(io::Error::last_os_error().into()) };
+    Ok(())
+}
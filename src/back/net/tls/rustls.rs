@@ -0,0 +1,210 @@
+//! TLS using rustls.
+
+use Error;
+use back::net::transport::Stream;
+use back::net::tls::TlsBackend;
+
+use std::io::prelude::*;
+use std::io;
+use std::sync::Arc;
+
+use mio;
+use rustls::{self, Session};
+use webpki;
+
+/// The name a device's certificate is checked against.
+///
+/// Devices are connected to by address, and the name is never checked,
+/// but rustls needs a DNS name to start a session with.
+const DEVICE_NAME: &'static str = "cast.device";
+
+/// Talks to devices using rustls.
+#[derive(Clone)]
+pub struct Rustls
+{
+    config: Arc<rustls::ClientConfig>,
+}
+
+/// Accepts the self-signed certificates that Cast devices present.
+struct CastCertificateVerifier;
+
+/// A TLS session over a non-blocking socket.
+struct TlsStream
+{
+    session: rustls::ClientSession,
+    socket: mio::tcp::TcpStream,
+    /// Whether the device has closed the socket.
+    closed: bool,
+}
+
+impl Rustls
+{
+    pub fn new() -> Self {
+        let mut config = rustls::ClientConfig::new();
+        config.dangerous().set_certificate_verifier(Arc::new(CastCertificateVerifier));
+
+        Rustls { config: Arc::new(config) }
+    }
+}
+
+impl Default for Rustls
+{
+    fn default() -> Self { Rustls::new() }
+}
+
+impl TlsBackend for Rustls
+{
+    fn connect(&self, socket: mio::tcp::TcpStream) -> Result<Box<Stream>, Error> {
+        let name = webpki::DNSNameRef::try_from_ascii_str(DEVICE_NAME).expect("the device name is valid");
+
+        let mut stream = TlsStream {
+            session: rustls::ClientSession::new(&self.config, name),
+            socket: socket,
+            closed: false,
+        };
+
+        // Start the handshake. It finishes as the stream is read from.
+        stream.transfer()?;
+        Ok(Box::new(stream))
+    }
+}
+
+impl rustls::ServerCertVerifier for CastCertificateVerifier
+{
+    fn verify_server_cert(&self,
+                          _roots: &rustls::RootCertStore,
+                          presented_certs: &[rustls::Certificate],
+                          _dns_name: webpki::DNSNameRef,
+                          _ocsp_response: &[u8]) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        // Each device signs its own certificate, so there is no chain to a
+        // certificate authority to check. A certificate must still be given.
+        if presented_certs.is_empty() {
+            Err(rustls::TLSError::NoCertificatesPresented)
+        } else {
+            Ok(rustls::ServerCertVerified::assertion())
+        }
+    }
+}
+
+impl TlsStream
+{
+    /// Moves TLS records between the session and the socket, for as long
+    /// as the socket doesn't block.
+    fn transfer(&mut self) -> io::Result<()> {
+        self.write_records()?;
+
+        while !self.closed && self.session.wants_read() {
+            match self.session.read_tls(&mut self.socket) {
+                Ok(0) => self.closed = true,
+                Ok(_) => {
+                    self.session.process_new_packets().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Reading can give the session something to answer, such as the
+        // next step of the handshake.
+        self.write_records()
+    }
+
+    /// Writes the records the session has for the socket.
+    fn write_records(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            match self.session.write_tls(&mut self.socket) {
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for TlsStream
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.transfer()?;
+
+        match self.session.read(buf) {
+            // The session has nothing for us until more arrives.
+            Ok(0) if !self.closed => Err(io::ErrorKind::WouldBlock.into()),
+            Ok(count) => Ok(count),
+            // The device closed the session cleanly.
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Write for TlsStream
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Data written before the handshake finishes is kept by the session.
+        let count = self.session.write(buf)?;
+        self.transfer()?;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.transfer()
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{CastCertificateVerifier, DEVICE_NAME};
+    use rustls::{self, ServerCertVerifier};
+    use webpki;
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn devices_connect_to_the_emulator() {
+        use Device;
+        use back::net::Io;
+        use back::net::tls::Rustls;
+        use emulator::{Emulator, Options};
+
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        let emulator = Emulator::start(Options::new()).unwrap();
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect_with_tls(emulator.device_info(), &mut io, Arc::new(Rustls::new())).unwrap();
+        device.update_status().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while device.status().is_none() {
+            assert!(Instant::now() < deadline, "the emulator never sent its status");
+
+            io.poll.poll(&mut io.events, Some(Duration::from_millis(20))).unwrap();
+            for event in io.events.iter() {
+                device.handle_io(event).unwrap();
+            }
+            device.update().unwrap();
+        }
+
+        assert_eq!(device.status(), Some(&emulator.status()));
+    }
+
+    fn verify(certificates: &[rustls::Certificate]) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        let name = webpki::DNSNameRef::try_from_ascii_str(DEVICE_NAME).unwrap();
+        CastCertificateVerifier.verify_server_cert(&rustls::RootCertStore::empty(), certificates, name, &[])
+    }
+
+    #[test]
+    fn self_signed_certificates_are_accepted() {
+        let certificate = rustls::Certificate(b"any certificate at all".to_vec());
+
+        assert!(verify(&[certificate]).is_ok());
+    }
+
+    #[test]
+    fn a_certificate_must_be_presented() {
+        assert!(verify(&[]).is_err());
+    }
+}
@@ -1,46 +1,74 @@
-use Error;
+use {Error, ErrorKind};
 use discovery;
 use back::net;
+use back::net::tls::{self, TlsBackend};
 
 use std::collections::VecDeque;
 use std::io::prelude::*;
+use std::sync::Arc;
 use std::{mem, io};
 use std;
 
 use mio;
-use openssl::ssl;
 
 use byteorder::{ByteOrder, BigEndian, WriteBytesExt};
 
-const CAST_PORT: u16 = 8009;
+/// The largest packet we will accept.
+///
+/// Cast devices limit messages to 64 KiB, so anything larger is
+/// a corrupted or malicious size prefix.
+pub const MAX_PACKET_SIZE: usize = 64 * 1024;
 
 /// The integer type used to prefix packet sizes.
 type SizePrefix = u32;
-type RawPacket = Vec<u8>;
+pub type RawPacket = Vec<u8>;
+
+/// A stream that a transport can send packets over.
+///
+/// Streams should not block: reads and writes that cannot happen yet
+/// should fail with `WouldBlock`, or `TimedOut` if the stream has a
+/// timeout. Any non-blocking `Read + Write` type is a stream, such as a
+/// `std::os::unix::net::UnixStream`, a `Pipe`, or a TLS stream from
+/// another library.
+pub trait Stream: Read + Write + Send { }
+
+impl<T> Stream for T where T: Read + Write + Send { }
 
 pub struct Transport
 {
-    token: mio::Token,
-    stream: Stream,
+    /// The token the stream is registered with mio under, if it is.
+    token: Option<mio::Token>,
+    stream: StreamState,
     reader: Reader,
 
     /// The packets that we have received so far.
     received_packets: VecDeque<RawPacket>,
-    /// The packets we need to send.
-    queued_packets: VecDeque<RawPacket>,
+    /// The size-prefixed packets we need to send.
+    outgoing: Vec<u8>,
+    /// How many bytes at the start of `outgoing` have been written.
+    ///
+    /// Streams may write only part of what they are given before they
+    /// block, so the rest is kept to be written later.
+    written: usize,
 }
 
-enum Stream
+enum StreamState
 {
     /// HACK TO APPEASE THE BORROW CHECKER.
+    ///
+    /// This is left behind if the TLS handshake fails.
     None,
     /// Waiting for the connection to be established.
-    PendingConnected(mio::tcp::TcpStream),
-    Connected(ssl::SslStream<mio::tcp::TcpStream>),
+    PendingConnected(mio::tcp::TcpStream, Arc<TlsBackend>),
+    Connected(Box<Stream>),
 }
 
+/// Splits a stream of bytes into size-prefixed packets.
+///
+/// The reader keeps partial packets between calls, so it can be used on
+/// non-blocking streams.
 #[derive(Debug, PartialEq, Eq)]
-enum Reader
+pub enum Reader
 {
     /// We are currently reading the size from the stream.
     ReadingSize {
@@ -60,6 +88,14 @@ impl Transport
 {
     pub fn new(stream: mio::tcp::TcpStream,
                io: &mut net::Io) -> Result<Self, Error> {
+        Transport::with_tls(stream, io, tls::default_backend())
+    }
+
+    /// Creates a transport that uses a particular TLS library once the
+    /// stream has connected.
+    pub fn with_tls(stream: mio::tcp::TcpStream,
+                    io: &mut net::Io,
+                    tls: Arc<TlsBackend>) -> Result<Self, Error> {
         let token = io.create_token();
 
         io.poll.register(&stream, token,
@@ -67,97 +103,198 @@ impl Transport
                          mio::PollOpt::level())?;
 
         Ok(Transport {
-            token: token,
-            stream: Stream::PendingConnected(stream),
+            token: Some(token),
+            stream: StreamState::PendingConnected(stream, tls),
             reader: Reader::new(),
             received_packets: VecDeque::new(),
-            queued_packets: VecDeque::new(),
+            outgoing: Vec::new(),
+            written: 0,
         })
     }
 
+    /// Creates a transport over a stream that is already connected.
+    ///
+    /// The stream is not registered with mio, so `update` must be called
+    /// regularly to send and receive packets.
+    pub fn from_stream<S>(stream: S) -> Self where S: Stream + 'static {
+        Transport {
+            token: None,
+            stream: StreamState::Connected(Box::new(stream)),
+            reader: Reader::new(),
+            received_packets: VecDeque::new(),
+            outgoing: Vec::new(),
+            written: 0,
+        }
+    }
+
     /// Connect to a Cast device that was discovered/
     pub fn connect_to(device: &discovery::DeviceInfo,
                       io: &mut net::Io) -> Result<Self, Error> {
+        Transport::connect_with_tls(device, io, tls::default_backend())
+    }
+
+    /// Connects to a Cast device using a particular TLS library.
+    pub fn connect_with_tls(device: &discovery::DeviceInfo,
+                            io: &mut net::Io,
+                            tls: Arc<TlsBackend>) -> Result<Self, Error> {
         let ip_addr = std::net::IpAddr::V4(device.ip_addr);
-        let socket_addr = std::net::SocketAddr::new(ip_addr, CAST_PORT);
+        let socket_addr = std::net::SocketAddr::new(ip_addr, device.port);
 
         let stream = mio::tcp::TcpStream::connect(&socket_addr)?;
 
-        Transport::new(stream, io)
+        Transport::with_tls(stream, io, tls)
     }
 
     pub fn send(&mut self, data: Vec<u8>) -> Result<(), Error> {
-        self.queued_packets.push_back(data);
-        Ok(())
+        write_packet(&mut self.outgoing, &data)
     }
 
     pub fn receive(&mut self) -> ::std::collections::vec_deque::Drain<Vec<u8>> {
         self.received_packets.drain(..)
     }
 
-    pub fn update(&mut self) {
-        if let Stream::Connected(ref mut stream) = self.stream {
-            let mut packets = Vec::new();
-            self.reader.read(stream, &mut packets).unwrap();
-            self.received_packets.extend(packets);
-        }
+    /// Sends the queued packets and reads the packets that are available.
+    pub fn update(&mut self) -> Result<(), Error> {
+        self.write_queued()?;
+        self.read_packets()
     }
 
     pub fn handle_event(&mut self, event: mio::Event)
         -> Result<(), Error> {
-        if event.token() == self.token {
+        if Some(event.token()) == self.token {
             if event.kind().is_readable() {
-                if let Stream::Connected(ref mut stream) = self.stream {
-                    let mut packets = Vec::new();
-                    self.reader.read(stream, &mut packets)?;
-                    self.received_packets.extend(packets);
-                }
+                self.read_packets()?;
             }
 
             if event.kind().is_writable() {
-                let current_stream = mem::replace(&mut self.stream, Stream::None);
+                let current_stream = mem::replace(&mut self.stream, StreamState::None);
 
                 self.stream = match current_stream {
-                    Stream::PendingConnected(stream) => {
-                        use std::os::unix::io::AsRawFd;
-                        use libc;
+                    StreamState::PendingConnected(stream, tls) => {
+                        StreamState::Connected(tls.connect(stream)?)
+                    },
+                    StreamState::Connected(stream) => StreamState::Connected(stream),
+                    StreamState::None => return Err(ErrorKind::ConnectionClosed.into()),
+                };
 
-                        let fd = stream.as_raw_fd();
+                self.write_queued()?;
+            }
+        } else {
+            unreachable!();
+        }
+        Ok(())
+    }
 
+    /// Writes as much of the queued data as the stream will take without
+    /// blocking.
+    fn write_queued(&mut self) -> Result<(), Error> {
+        if let StreamState::Connected(ref mut stream) = self.stream {
+            while self.written < self.outgoing.len() {
+                match stream.write(&self.outgoing[self.written..]) {
+                    Ok(0) => return Err(ErrorKind::ConnectionClosed.into()),
+                    Ok(n) => self.written += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                                  e.kind() == io::ErrorKind::TimedOut => break,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
 
-                        // SslStream doesn't seem to properly support non-blocking sockets.
-                        // We temporarily turn off the flag until the handshake finishes.
-                        let status = unsafe { libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL, 0) & !libc::O_NONBLOCK) };
-                        if status == -1 { panic!("could not mark socket as blocking"); }
+            match stream.flush() {
+                Ok(()) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                              e.kind() == io::ErrorKind::TimedOut => (),
+                Err(e) => return Err(e.into()),
+            }
 
-                        let mut connector_builder = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls()).unwrap();
-                        connector_builder.builder_mut().set_verify(ssl::SSL_VERIFY_NONE);
+            if self.written == self.outgoing.len() {
+                self.outgoing.clear();
+                self.written = 0;
+            }
+        }
 
-                        let connector = connector_builder.build();
-                        let ssl_stream = connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream).unwrap();
+        Ok(())
+    }
 
-                        let status = unsafe { libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL, 0) | libc::O_NONBLOCK) };
-                        if status == -1 { panic!("could not mark socket as non-blocking"); }
+    /// Reads all of the packets that are available.
+    ///
+    /// Packets that arrived before the connection was closed can
+    /// still be taken with `receive`.
+    fn read_packets(&mut self) -> Result<(), Error> {
+        if let StreamState::Connected(ref mut stream) = self.stream {
+            let mut packets = Vec::new();
+            let end_of_stream = self.reader.read(stream, &mut packets)?;
+            self.received_packets.extend(packets);
 
-                        Stream::Connected(ssl_stream)
-                    },
-                    Stream::Connected(mut stream) => {
-                        if let Some(raw_packet) = self.queued_packets.pop_front() {
-                            stream.write_u32::<BigEndian>(raw_packet.len() as u32)?;
-                            stream.write(&raw_packet)?;
-                        }
-                        Stream::Connected(stream)
-                    },
-                    Stream::None => unreachable!(),
-                };
+            if end_of_stream {
+                self.reader.finish()?;
+                return Err(ErrorKind::ConnectionClosed.into());
             }
-        } else {
-            unreachable!();
         }
+
         Ok(())
     }
 }
 
+/// Writes a packet, prefixed with its size.
+pub fn write_packet(write: &mut Write, packet: &[u8]) -> Result<(), Error> {
+    write.write_u32::<BigEndian>(packet.len() as SizePrefix)?;
+    write.write_all(packet)?;
+    write.flush()?;
+    Ok(())
+}
+
+/// Reads a single packet from a blocking stream.
+///
+/// Returns `Ok(None)` if the stream was closed before a new packet began.
+pub fn read_packet(read: &mut Read) -> Result<Option<RawPacket>, Error> {
+    let mut size_bytes = [0; 4];
+
+    match read.read_exact(&mut size_bytes) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let size = BigEndian::read_u32(&size_bytes) as usize;
+    if size > MAX_PACKET_SIZE { return Err(ErrorKind::PacketTooLarge(size).into()) };
+
+    let mut packet = vec![0; size];
+    read.read_exact(&mut packet)?;
+    Ok(Some(packet))
+}
+
+/// Reads up to `count` bytes that are available without blocking.
+///
+/// Bytes that were read before the stream would block are kept.
+/// Returns `Ok(true)` if the end of the stream was reached.
+fn read_available(read: &mut Read, bytes: &mut Vec<u8>, count: usize) -> Result<bool, Error> {
+    let mut buffer = vec![0; count];
+    let mut total = 0;
+    let mut end_of_stream = false;
+
+    while total < count {
+        match read.read(&mut buffer[total..]) {
+            Ok(0) => {
+                end_of_stream = true;
+                break;
+            },
+            Ok(n) => total += n,
+            // Read timeouts are reported as `TimedOut` on some platforms.
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                          e.kind() == io::ErrorKind::TimedOut => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                bytes.extend(&buffer[..total]);
+                return Err(e.into());
+            },
+        }
+    }
+
+    bytes.extend(&buffer[..total]);
+    Ok(end_of_stream)
+}
+
 impl Reader
 {
     pub fn new() -> Self {
@@ -165,14 +302,33 @@ impl Reader
     }
 
     /// Attempts to read data from a stream into a list of packets.
+    ///
+    /// Returns `Ok(true)` if the end of the stream was reached. On a
+    /// socket this means the connection was closed, and `finish` should
+    /// be called to check that no packet was cut off.
     pub fn read(&mut self,
                 read: &mut Read,
-                packets: &mut Vec<RawPacket>) -> Result<(), Error> {
+                packets: &mut Vec<RawPacket>) -> Result<bool, Error> {
+        let mut end_of_stream = false;
+
         loop {
-            if !self.progress_state(read, packets)? { break };
+            if !self.progress_state(read, packets, &mut end_of_stream)? { break };
         }
 
-        Ok(())
+        Ok(end_of_stream)
+    }
+
+    /// Checks that the stream did not end in the middle of a packet.
+    pub fn finish(&self) -> Result<(), Error> {
+        match *self {
+            Reader::ReadingSize { ref bytes } if bytes.is_empty() => Ok(()),
+            Reader::ReadingSize { ref bytes } => {
+                Err(ErrorKind::TruncatedPacket(mem::size_of::<SizePrefix>(), bytes.len()).into())
+            },
+            Reader::ReadingBody { ref bytes, size } => {
+                Err(ErrorKind::TruncatedPacket(size, bytes.len()).into())
+            },
+        }
     }
 
     /// Reads data from the stream and attempts to move to
@@ -182,29 +338,32 @@ impl Reader
     /// data to progress to the next state.
     fn progress_state(&mut self,
                       read: &mut Read,
-                      packets: &mut Vec<RawPacket>) -> Result<bool, Error> {
+                      packets: &mut Vec<RawPacket>,
+                      end_of_stream: &mut bool) -> Result<bool, Error> {
         let current_state = mem::replace(self, Reader::new());
 
         let (can_progress, new_state) = match current_state {
             Reader::ReadingSize { mut bytes } => {
                 let bytes_remaining = mem::size_of::<SizePrefix>() - bytes.len();
 
-                let extra_bytes: Result<Vec<_>, _> = read.bytes().take(bytes_remaining).collect();
-
-                match extra_bytes {
-                    Ok(extra_bytes) => bytes.extend(extra_bytes),
-                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                        // do nothing
+                match read_available(read, &mut bytes, bytes_remaining) {
+                    Ok(eof) => *end_of_stream |= eof,
+                    Err(e) => {
+                        *self = Reader::ReadingSize { bytes: bytes };
+                        return Err(e);
                     },
-                    Err(e) => return Err(e)?,
                 }
 
                 if bytes.len() == mem::size_of::<SizePrefix>() {
-                    let body_size = BigEndian::read_u32(&bytes);
+                    let body_size = BigEndian::read_u32(&bytes) as usize;
+
+                    if body_size > MAX_PACKET_SIZE {
+                        return Err(ErrorKind::PacketTooLarge(body_size).into());
+                    }
 
                     (true, Reader::ReadingBody {
                         bytes: Vec::new(),
-                        size: body_size as usize,
+                        size: body_size,
                     })
                 } else {
                     (false, Reader::ReadingSize { bytes: bytes })
@@ -213,8 +372,13 @@ impl Reader
             Reader::ReadingBody { mut bytes, size } => {
                 let bytes_remaining = size - bytes.len();
 
-                let extra_bytes: Result<Vec<_>, _> = read.bytes().take(bytes_remaining).collect();
-                bytes.extend(extra_bytes?);
+                match read_available(read, &mut bytes, bytes_remaining) {
+                    Ok(eof) => *end_of_stream |= eof,
+                    Err(e) => {
+                        *self = Reader::ReadingBody { bytes: bytes, size: size };
+                        return Err(e);
+                    },
+                }
 
                 if bytes.len() == size {
                     // We have finished reading a packet.
@@ -278,6 +442,92 @@ mod test
             assert_eq!(packets, vec!["hello".as_bytes()]);
         }
 
+        /// A stream that gives out its data in chunks, blocking after each one.
+        struct Chunked(Vec<Vec<u8>>);
+
+        impl io::Read for Chunked {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() { return Ok(0) };
+
+                if self.0[0].is_empty() {
+                    self.0.remove(0);
+                    return Err(io::Error::new(io::ErrorKind::WouldBlock, "no data yet"));
+                }
+
+                let n = ::std::cmp::min(buf.len(), self.0[0].len());
+                buf[..n].copy_from_slice(&self.0[0][..n]);
+                self.0[0].drain(..n);
+                Ok(n)
+            }
+        }
+
+        #[test]
+        fn it_keeps_bytes_read_before_blocking() {
+            let mut stream = Chunked(vec![vec![0, 0], vec![0, 3, b'a'], vec![b'b', b'c']]);
+            let mut reader = Reader::new();
+            let mut packets = Vec::new();
+
+            reader.read(&mut stream, &mut packets).unwrap();
+            assert_eq!(reader, Reader::ReadingSize { bytes: vec![0, 0] });
+
+            reader.read(&mut stream, &mut packets).unwrap();
+            assert_eq!(reader, Reader::ReadingBody { size: 3, bytes: vec![b'a'] });
+
+            reader.read(&mut stream, &mut packets).unwrap();
+            assert_eq!(packets, vec![b"abc".to_vec()]);
+        }
+
+        #[test]
+        fn it_refuses_oversized_packets() {
+            let mut reader = Reader::new();
+            let result = reader.read(&mut io::Cursor::new([0xff, 0xff, 0xff, 0xff, 0]), &mut Vec::new());
+
+            match result.unwrap_err().kind() {
+                &::ErrorKind::PacketTooLarge(size) => assert_eq!(size, 0xffffffff),
+                kind => panic!("unexpected error: {:?}", kind),
+            }
+        }
+
+        #[test]
+        fn it_notices_packets_cut_off_by_the_end_of_the_stream() {
+            let (reader, _) = read_data("\x00\x00\x00\x05wo".as_bytes());
+
+            match reader.finish().unwrap_err().kind() {
+                &::ErrorKind::TruncatedPacket(expected, received) => assert_eq!((expected, received), (5, 2)),
+                kind => panic!("unexpected error: {:?}", kind),
+            }
+        }
+
+        #[test]
+        fn it_notices_size_prefixes_cut_off_by_the_end_of_the_stream() {
+            let (reader, _) = read_data(&[0, 0]);
+            assert!(reader.finish().is_err());
+
+            let (reader, _) = read_data("\x00\x00\x00\x05hello".as_bytes());
+            assert!(reader.finish().is_ok());
+        }
+
+        #[test]
+        fn it_reports_the_end_of_the_stream() {
+            let mut reader = Reader::new();
+            assert!(reader.read(&mut io::Cursor::new([0, 0, 0, 1, 7]), &mut Vec::new()).unwrap());
+
+            let mut stream = Chunked(vec![vec![0, 0]]);
+            assert!(!reader.read(&mut stream, &mut Vec::new()).unwrap());
+        }
+
+        #[test]
+        fn written_packets_can_be_read_back() {
+            let mut data = Vec::new();
+            super::super::write_packet(&mut data, b"hello").unwrap();
+            super::super::write_packet(&mut data, b"").unwrap();
+
+            let mut cursor = io::Cursor::new(data);
+            assert_eq!(super::super::read_packet(&mut cursor).unwrap(), Some(b"hello".to_vec()));
+            assert_eq!(super::super::read_packet(&mut cursor).unwrap(), Some(Vec::new()));
+            assert_eq!(super::super::read_packet(&mut cursor).unwrap(), None);
+        }
+
         #[test]
         fn it_reads_body_in_pieces() {
             let (mut reader, mut packets) = read_data("\x00\x00\x00\x05wo".as_bytes());
@@ -291,4 +541,87 @@ mod test
             assert_eq!(packets, vec!["world".as_bytes()]);
         }
     }
+
+    mod transport
+    {
+        use super::super::{Transport, Reader, write_packet};
+        use back::net::pipe;
+        use ErrorKind;
+
+        use std::io;
+        use std::sync::{Arc, Mutex};
+
+        /// A stream that takes a few bytes at a time, blocking in between.
+        struct Trickle
+        {
+            written: Arc<Mutex<Vec<u8>>>,
+            blocked: bool,
+        }
+
+        impl io::Read for Trickle {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "no data yet"))
+            }
+        }
+
+        impl io::Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.blocked = !self.blocked;
+                if !self.blocked {
+                    return Err(io::Error::new(io::ErrorKind::WouldBlock, "buffer full"));
+                }
+
+                let n = ::std::cmp::min(buf.len(), 3);
+                self.written.lock().unwrap().extend(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+
+        #[test]
+        fn it_sends_and_receives_over_any_stream() {
+            let (stream, mut device) = pipe();
+            let mut transport = Transport::from_stream(stream);
+
+            transport.send(b"hello".to_vec()).unwrap();
+            transport.update().unwrap();
+
+            let mut packets = Vec::new();
+            Reader::new().read(&mut device, &mut packets).unwrap();
+            assert_eq!(packets, vec![b"hello".to_vec()]);
+
+            write_packet(&mut device, b"world").unwrap();
+            transport.update().unwrap();
+            assert_eq!(transport.receive().collect::<Vec<_>>(), vec![b"world".to_vec()]);
+        }
+
+        #[test]
+        fn it_keeps_unsent_bytes_when_the_stream_blocks() {
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let mut transport = Transport::from_stream(Trickle { written: written.clone(), blocked: false });
+            transport.send(b"hello".to_vec()).unwrap();
+            transport.send(b"world".to_vec()).unwrap();
+
+            for _ in 0..10 {
+                transport.update().unwrap();
+            }
+
+            let mut packets = Vec::new();
+            Reader::new().read(&mut io::Cursor::new(written.lock().unwrap().clone()), &mut packets).unwrap();
+            assert_eq!(packets, vec![b"hello".to_vec(), b"world".to_vec()]);
+        }
+
+        #[test]
+        fn it_notices_the_stream_closing() {
+            let (stream, device) = pipe();
+            let mut transport = Transport::from_stream(stream);
+            drop(device);
+
+            match *transport.update().unwrap_err().kind() {
+                ErrorKind::ConnectionClosed => (),
+                ref kind => panic!("unexpected error: {:?}", kind),
+            }
+        }
+    }
 }
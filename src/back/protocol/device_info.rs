@@ -0,0 +1,69 @@
+use Error;
+use field::Field;
+
+use json;
+
+/// Bit flags used in `DeviceDescription::capabilities`.
+pub mod capability {
+    pub const VIDEO_OUT: u64 = 1 << 0;
+    pub const VIDEO_IN: u64 = 1 << 1;
+    pub const AUDIO_OUT: u64 = 1 << 2;
+    pub const AUDIO_IN: u64 = 1 << 3;
+    pub const DEV_MODE: u64 = 1 << 4;
+    pub const MULTIZONE_GROUP: u64 = 1 << 5;
+}
+
+/// Information a receiver gives about itself in response to `GET_DEVICE_INFO`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceDescription
+{
+    /// The unique identifier of the device.
+    pub device_id: String,
+    /// The name the user has given the device.
+    pub friendly_name: String,
+    /// The model of the device.
+    ///
+    /// Example: `Chromecast`.
+    pub model: Option<String>,
+    /// The features of the device, as `capability` flags.
+    pub capabilities: Option<u64>,
+    /// The notifications that the device will send to senders.
+    pub control_notifications: Option<i64>,
+    /// The path to the device icon on the device's HTTP server.
+    pub icon_url: Option<String>,
+    /// An identifier used by the device when reporting metrics.
+    pub receiver_metrics_id: Option<String>,
+}
+
+impl DeviceDescription
+{
+    /// Reads the description from the payload of a `DEVICE_INFO` message.
+    pub fn from_field(info: &Field) -> Result<Self, Error> {
+        Ok(DeviceDescription {
+            device_id: info.get("deviceId").as_str()?.to_owned(),
+            friendly_name: info.get("friendlyName").as_str()?.to_owned(),
+            model: info.get("deviceModel").optional(Field::as_str)?.map(ToOwned::to_owned),
+            capabilities: info.get("deviceCapabilities").optional(Field::as_i64)?.map(|capabilities| capabilities as u64),
+            control_notifications: info.get("controlNotifications").optional(Field::as_i64)?,
+            icon_url: info.get("deviceIconUrl").optional(Field::as_str)?.map(ToOwned::to_owned),
+            receiver_metrics_id: info.get("receiverMetricsId").optional(Field::as_str)?.map(ToOwned::to_owned),
+        })
+    }
+
+    /// Adds the description to a `DEVICE_INFO` message payload.
+    pub fn write_to(&self, payload: &mut json::JsonValue) {
+        payload["deviceId"] = self.device_id.clone().into();
+        payload["friendlyName"] = self.friendly_name.clone().into();
+
+        if let Some(ref model) = self.model { payload["deviceModel"] = model.clone().into() };
+        if let Some(capabilities) = self.capabilities { payload["deviceCapabilities"] = (capabilities as i64).into() };
+        if let Some(control_notifications) = self.control_notifications { payload["controlNotifications"] = control_notifications.into() };
+        if let Some(ref icon_url) = self.icon_url { payload["deviceIconUrl"] = icon_url.clone().into() };
+        if let Some(ref receiver_metrics_id) = self.receiver_metrics_id { payload["receiverMetricsId"] = receiver_metrics_id.clone().into() };
+    }
+
+    /// Checks whether the device has a capability from the `capability` module.
+    pub fn has_capability(&self, capability: u64) -> bool {
+        self.capabilities.map(|capabilities| capabilities & capability != 0).unwrap_or(false)
+    }
+}
@@ -6,12 +6,23 @@ error_chain! {
     foreign_links {
         ::protobuf::error::ProtobufError, Protobuf;
         ::uuid::ParseError, UuidParseError;
+        ::json::Error, Json;
     }
 
     errors {
-        UnknownMessageType(ty: String) {
-            description("unknown message type")
-            display("unknown message type: '{}'", ty)
+        MissingField(path: String) {
+            description("missing field")
+            display("missing field: '{}'", path)
+        }
+
+        InvalidFieldType(path: String, expected: &'static str) {
+            description("field has the wrong type")
+            display("expected field '{}' to be {}", path, expected)
+        }
+
+        InvalidFieldValue(path: String, reason: String) {
+            description("field has an invalid value")
+            display("invalid value for field '{}': {}", path, reason)
         }
     }
 }
@@ -0,0 +1,116 @@
+//! Helpers for reading fields out of JSON payloads.
+
+use {Error, ErrorKind};
+
+use json::JsonValue;
+use uuid::Uuid;
+
+/// A JSON value along with the path that was taken to reach it.
+///
+/// The path is used to describe the field in error messages,
+/// for example `status.applications[0].sessionId`.
+#[derive(Clone, Debug)]
+pub struct Field<'a>
+{
+    /// The value of the field.
+    pub value: &'a JsonValue,
+    /// The path to the field from the root of the payload.
+    pub path: String,
+}
+
+impl<'a> Field<'a>
+{
+    /// Creates a field at the root of a payload.
+    pub fn root(value: &'a JsonValue, path: &str) -> Self {
+        Field { value: value, path: path.to_owned() }
+    }
+
+    /// Gets a member of an object.
+    pub fn get(&self, key: &str) -> Field<'a> {
+        let path = if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+
+        Field { value: &self.value[key], path: path }
+    }
+
+    /// Checks whether the field has been given a value.
+    pub fn is_present(&self) -> bool { !self.value.is_null() }
+
+    /// Gets the elements of an array.
+    pub fn as_array(&self) -> Result<Vec<Field<'a>>, Error> {
+        self.require()?;
+
+        if !self.value.is_array() {
+            return Err(self.invalid_type("an array"));
+        }
+
+        Ok(self.value.members().enumerate().map(|(index, value)| Field {
+            value: value,
+            path: format!("{}[{}]", self.path, index),
+        }).collect())
+    }
+
+    pub fn as_str(&self) -> Result<&'a str, Error> {
+        self.require()?;
+        self.value.as_str().ok_or_else(|| self.invalid_type("a string"))
+    }
+
+    pub fn as_bool(&self) -> Result<bool, Error> {
+        self.require()?;
+        self.value.as_bool().ok_or_else(|| self.invalid_type("a bool"))
+    }
+
+    pub fn as_f32(&self) -> Result<f32, Error> {
+        self.require()?;
+        self.value.as_f32().ok_or_else(|| self.invalid_type("a number"))
+    }
+
+    pub fn as_f64(&self) -> Result<f64, Error> {
+        self.require()?;
+        self.value.as_f64().ok_or_else(|| self.invalid_type("a number"))
+    }
+
+    pub fn as_i64(&self) -> Result<i64, Error> {
+        self.require()?;
+        self.value.as_i64().ok_or_else(|| self.invalid_type("an integer"))
+    }
+
+    /// Gets an array of integers that each fit in a byte.
+    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.as_array()?.iter().map(|byte| {
+            match byte.as_i64()? {
+                value @ 0...255 => Ok(value as u8),
+                value => Err(ErrorKind::InvalidFieldValue(byte.path.clone(), format!("{} is not a byte", value)).into()),
+            }
+        }).collect()
+    }
+
+    pub fn as_uuid(&self) -> Result<Uuid, Error> {
+        let text = self.as_str()?;
+
+        Uuid::parse_str(text).map_err(|e| {
+            ErrorKind::InvalidFieldValue(self.path.clone(), e.to_string()).into()
+        })
+    }
+
+    /// Reads an optional field, returning `None` if it is missing.
+    pub fn optional<T, F>(&self, f: F) -> Result<Option<T>, Error>
+        where F: FnOnce(&Self) -> Result<T, Error> {
+        if self.is_present() { f(self).map(Some) } else { Ok(None) }
+    }
+
+    fn require(&self) -> Result<(), Error> {
+        if self.is_present() {
+            Ok(())
+        } else {
+            Err(ErrorKind::MissingField(self.path.clone()).into())
+        }
+    }
+
+    fn invalid_type(&self, expected: &'static str) -> Error {
+        ErrorKind::InvalidFieldType(self.path.clone(), expected).into()
+    }
+}
@@ -0,0 +1,184 @@
+use {Error, ErrorKind};
+use field::Field;
+
+use json;
+
+/// Options that can be given when launching an application.
+///
+/// ```
+/// # use gcast_protocol::launch::{LaunchOptions, AppType};
+/// let options = LaunchOptions::new()
+///     .language("en-US")
+///     .supported_app_types(&[AppType::Web, AppType::AndroidTv]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LaunchOptions
+{
+    /// The language the application should use, as an IETF BCP 47 tag.
+    ///
+    /// Example: `en-US`.
+    pub language: Option<String>,
+    /// The types of application the sender is able to talk to.
+    ///
+    /// Google TV receivers refuse to launch Android TV applications unless
+    /// `AppType::AndroidTv` is listed here.
+    pub supported_app_types: Vec<AppType>,
+    /// Parameters passed to Android TV applications.
+    pub app_params: Option<json::JsonValue>,
+    /// Credentials passed to the application.
+    pub credentials: Option<Credentials>,
+}
+
+/// A type of receiver application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AppType
+{
+    /// A web receiver application.
+    Web,
+    /// A native Android TV application.
+    AndroidTv,
+    /// An application type that this crate does not know about.
+    Other(String),
+}
+
+/// Credentials that are passed to an application when it is launched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Credentials
+{
+    /// The credentials themselves.
+    pub credentials: String,
+    /// The type of credentials.
+    ///
+    /// Example: `android`.
+    pub credentials_type: Option<String>,
+}
+
+impl LaunchOptions
+{
+    /// Creates a set of options that leaves everything up to the receiver.
+    pub fn new() -> Self { LaunchOptions::default() }
+
+    /// Sets the language the application should use.
+    pub fn language<S>(mut self, language: S) -> Self where S: Into<String> {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Sets the types of application the sender supports.
+    pub fn supported_app_types(mut self, app_types: &[AppType]) -> Self {
+        self.supported_app_types = app_types.to_owned();
+        self
+    }
+
+    /// Sets the parameters passed to Android TV applications.
+    pub fn app_params(mut self, app_params: json::JsonValue) -> Self {
+        self.app_params = Some(app_params);
+        self
+    }
+
+    /// Sets the credentials passed to the application.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Reads the options out of a `LAUNCH` message payload.
+    pub fn from_field(payload: &Field) -> Result<Self, Error> {
+        let supported_app_types = match payload.get("supportedAppTypes").optional(Field::as_array)? {
+            Some(app_type_fields) => {
+                let result: Result<Vec<_>, Error> = app_type_fields.iter().map(|app_type| {
+                    Ok(AppType::from(app_type.as_str()?))
+                }).collect();
+                result?
+            },
+            None => Vec::new(),
+        };
+
+        let credentials = payload.get("credentialsData");
+        let credentials = if credentials.is_present() {
+            Some(Credentials {
+                credentials: credentials.get("credentials").as_str()?.to_owned(),
+                credentials_type: credentials.get("credentialsType").optional(Field::as_str)?.map(ToOwned::to_owned),
+            })
+        } else {
+            None
+        };
+
+        let app_params = payload.get("appParams");
+        if app_params.is_present() && !app_params.value.is_object() {
+            return Err(ErrorKind::InvalidFieldType(app_params.path.clone(), "an object").into());
+        }
+
+        Ok(LaunchOptions {
+            language: payload.get("language").optional(Field::as_str)?.map(ToOwned::to_owned),
+            supported_app_types: supported_app_types,
+            app_params: app_params.optional(|field| Ok(field.value.clone()))?,
+            credentials: credentials,
+        })
+    }
+
+    /// Adds the options to a `LAUNCH` message payload.
+    pub fn write_to(&self, payload: &mut json::JsonValue) {
+        if let Some(ref language) = self.language {
+            payload["language"] = language.clone().into();
+        }
+
+        if !self.supported_app_types.is_empty() {
+            let app_types: Vec<_> = self.supported_app_types.iter().map(|app_type| app_type.as_str().to_owned()).collect();
+            payload["supportedAppTypes"] = app_types.into();
+        }
+
+        if let Some(ref app_params) = self.app_params {
+            payload["appParams"] = app_params.clone();
+        }
+
+        if let Some(ref credentials) = self.credentials {
+            let mut credentials_data = object! {
+                "credentials" => &credentials.credentials[..]
+            };
+
+            if let Some(ref credentials_type) = credentials.credentials_type {
+                credentials_data["credentialsType"] = credentials_type.clone().into();
+            }
+
+            payload["credentialsData"] = credentials_data;
+        }
+    }
+}
+
+impl AppType
+{
+    /// Gets the name used for the application type in messages.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            AppType::Web => "WEB",
+            AppType::AndroidTv => "ANDROID_TV",
+            AppType::Other(ref app_type) => &app_type[..],
+        }
+    }
+}
+
+impl<'a> From<&'a str> for AppType
+{
+    fn from(app_type: &'a str) -> Self {
+        match app_type {
+            "WEB" => AppType::Web,
+            "ANDROID_TV" => AppType::AndroidTv,
+            other => AppType::Other(other.to_owned()),
+        }
+    }
+}
+
+impl Credentials
+{
+    /// Creates a new set of credentials.
+    pub fn new<S>(credentials: S) -> Self where S: Into<String> {
+        Credentials { credentials: credentials.into(), credentials_type: None }
+    }
+
+    /// Sets the type of the credentials.
+    pub fn credentials_type<S>(mut self, credentials_type: S) -> Self where S: Into<String> {
+        self.credentials_type = Some(credentials_type.into());
+        self
+    }
+}
@@ -0,0 +1,244 @@
+use {VolumeLevel, Error, ErrorKind};
+use field::Field;
+
+use json;
+
+/// An identifier for a media session, unique within an application.
+pub type MediaSessionId = i64;
+
+/// The status of a media session, as sent in `MEDIA_STATUS` messages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaStatus
+{
+    /// The identifier of the media session.
+    pub media_session_id: MediaSessionId,
+    /// Whether the media is playing, paused, etc.
+    pub player_state: PlayerState,
+    /// Why the player is idle, if it is.
+    ///
+    /// Example: `FINISHED`.
+    pub idle_reason: Option<String>,
+    /// The position in the media, in seconds.
+    pub current_time: f64,
+    /// The speed the media is played at, where `1.0` is normal speed.
+    pub playback_rate: f32,
+    /// The volume of the media stream.
+    ///
+    /// This is separate from the volume of the device, which is
+    /// reported in `Status::volume`.
+    pub volume: MediaVolume,
+    /// The commands the media player supports, as a bitmask.
+    pub supported_media_commands: u64,
+    /// Information about the media itself, such as its content ID.
+    ///
+    /// Receivers often leave this out once it has been sent.
+    pub media: Option<json::JsonValue>,
+    /// The part of a live stream that can be seeked to.
+    ///
+    /// This is only present for live streams.
+    pub live_seekable_range: Option<LiveSeekableRange>,
+}
+
+/// The part of a live stream that can be seeked to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LiveSeekableRange
+{
+    /// The earliest position that can be seeked to, in seconds.
+    pub start: Option<f64>,
+    /// The latest position that can be seeked to, in seconds.
+    ///
+    /// This is the live edge of the stream.
+    pub end: Option<f64>,
+    /// Whether the range moves forward as the stream goes on.
+    pub is_moving_window: Option<bool>,
+    /// Whether the live stream has ended.
+    pub is_live_done: Option<bool>,
+}
+
+/// A position to seek to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SeekPosition
+{
+    /// A position from the start of the media, in seconds.
+    Absolute(f64),
+    /// A number of seconds from the current position.
+    ///
+    /// Negative values seek backwards.
+    Relative(f64),
+}
+
+/// Whether playback should continue after a seek.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResumeState
+{
+    /// Start playing after seeking.
+    PlaybackStart,
+    /// Pause after seeking.
+    PlaybackPause,
+}
+
+/// The volume of a media stream.
+///
+/// A receiver may leave out either field if it does not control the
+/// volume of the stream.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MediaVolume
+{
+    /// The volume level of the stream.
+    pub level: Option<VolumeLevel>,
+    /// Whether the stream is muted.
+    pub muted: Option<bool>,
+}
+
+/// The state of a media player.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlayerState
+{
+    /// Nothing is loaded, or playback has finished.
+    Idle,
+    Playing,
+    Paused,
+    /// Playback is waiting for more data.
+    Buffering,
+    /// A state that this crate does not know about.
+    Other(String),
+}
+
+impl MediaStatus
+{
+    pub fn from_field(status: &Field) -> Result<Self, Error> {
+        let media = status.get("media");
+        let volume = status.get("volume");
+
+        Ok(MediaStatus {
+            media_session_id: status.get("mediaSessionId").as_i64()?,
+            player_state: PlayerState::from(status.get("playerState").as_str()?),
+            idle_reason: status.get("idleReason").optional(Field::as_str)?.map(ToOwned::to_owned),
+            current_time: status.get("currentTime").optional(Field::as_f64)?.unwrap_or(0.0),
+            playback_rate: status.get("playbackRate").optional(Field::as_f32)?.unwrap_or(1.0),
+            volume: MediaVolume {
+                level: volume.get("level").optional(Field::as_f32)?.map(VolumeLevel),
+                muted: volume.get("muted").optional(Field::as_bool)?,
+            },
+            supported_media_commands: status.get("supportedMediaCommands").optional(Field::as_i64)?.unwrap_or(0) as u64,
+            media: media.optional(|field| Ok(field.value.clone()))?,
+            live_seekable_range: status.get("liveSeekableRange").optional(LiveSeekableRange::from_field)?,
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut volume = object! { };
+        if let Some(level) = self.volume.level { volume["level"] = level.0.into() };
+        if let Some(muted) = self.volume.muted { volume["muted"] = muted.into() };
+
+        let mut status = object! {
+            "mediaSessionId" => self.media_session_id,
+            "playerState" => self.player_state.as_str(),
+            "currentTime" => self.current_time,
+            "playbackRate" => self.playback_rate,
+            "volume" => volume,
+            "supportedMediaCommands" => self.supported_media_commands as i64
+        };
+
+        if let Some(ref idle_reason) = self.idle_reason { status["idleReason"] = idle_reason.clone().into() };
+        if let Some(ref media) = self.media { status["media"] = media.clone() };
+        if let Some(ref range) = self.live_seekable_range { status["liveSeekableRange"] = range.to_json() };
+
+        status
+    }
+
+    /// Gets the length of the media in seconds, if it is known.
+    ///
+    /// This is read from the `media` information and is not known for live streams.
+    pub fn duration(&self) -> Option<f64> {
+        self.media.as_ref().and_then(|media| media["duration"].as_f64())
+    }
+}
+
+impl LiveSeekableRange
+{
+    pub fn from_field(range: &Field) -> Result<Self, Error> {
+        Ok(LiveSeekableRange {
+            start: range.get("start").optional(Field::as_f64)?,
+            end: range.get("end").optional(Field::as_f64)?,
+            is_moving_window: range.get("isMovingWindow").optional(Field::as_bool)?,
+            is_live_done: range.get("isLiveDone").optional(Field::as_bool)?,
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut range = object! { };
+
+        if let Some(start) = self.start { range["start"] = start.into() };
+        if let Some(end) = self.end { range["end"] = end.into() };
+        if let Some(is_moving_window) = self.is_moving_window { range["isMovingWindow"] = is_moving_window.into() };
+        if let Some(is_live_done) = self.is_live_done { range["isLiveDone"] = is_live_done.into() };
+
+        range
+    }
+}
+
+impl SeekPosition
+{
+    /// Reads the position out of a `SEEK` message payload.
+    pub fn from_field(payload: &Field) -> Result<Self, Error> {
+        match payload.get("relativeTime").optional(Field::as_f64)? {
+            Some(relative_time) => Ok(SeekPosition::Relative(relative_time)),
+            None => Ok(SeekPosition::Absolute(payload.get("currentTime").as_f64()?)),
+        }
+    }
+
+    /// Adds the position to a `SEEK` message payload.
+    pub fn write_to(&self, payload: &mut json::JsonValue) {
+        match *self {
+            SeekPosition::Absolute(time) => payload["currentTime"] = time.into(),
+            SeekPosition::Relative(time) => payload["relativeTime"] = time.into(),
+        }
+    }
+}
+
+impl ResumeState
+{
+    pub fn from_field(field: &Field) -> Result<Self, Error> {
+        match field.as_str()? {
+            "PLAYBACK_START" => Ok(ResumeState::PlaybackStart),
+            "PLAYBACK_PAUSE" => Ok(ResumeState::PlaybackPause),
+            other => Err(ErrorKind::InvalidFieldValue(field.path.clone(), other.to_owned()).into()),
+        }
+    }
+
+    /// Gets the name used for the resume state in messages.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ResumeState::PlaybackStart => "PLAYBACK_START",
+            ResumeState::PlaybackPause => "PLAYBACK_PAUSE",
+        }
+    }
+}
+
+impl PlayerState
+{
+    /// Gets the name used for the state in messages.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            PlayerState::Idle => "IDLE",
+            PlayerState::Playing => "PLAYING",
+            PlayerState::Paused => "PAUSED",
+            PlayerState::Buffering => "BUFFERING",
+            PlayerState::Other(ref state) => &state[..],
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PlayerState
+{
+    fn from(state: &'a str) -> Self {
+        match state {
+            "IDLE" => PlayerState::Idle,
+            "PLAYING" => PlayerState::Playing,
+            "PAUSED" => PlayerState::Paused,
+            "BUFFERING" => PlayerState::Buffering,
+            other => PlayerState::Other(other.to_owned()),
+        }
+    }
+}
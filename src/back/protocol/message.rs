@@ -1,28 +1,34 @@
-use {Status, ApplicationId, SessionId, VolumeLevel, Error, ErrorKind};
+use {Status, ApplicationId, AppAvailability, RequestId, SessionId, VolumeLevel, Error, ErrorKind};
 
+use field::Field;
+use launch::LaunchOptions;
+use request_error::{RequestError, InvalidRequestReason, LaunchErrorReason};
+use device_info::DeviceDescription;
+use multizone::{GroupStatus, Member};
+use media::{MediaStatus, MediaSessionId, SeekPosition, ResumeState};
 use wire;
 
+use std::collections::HashMap;
+
 use protobuf;
 use json;
 
-use uuid::Uuid;
-
 /// The version of the CAST protocol we are using.
-const PROTOCOL_VERSION: wire::CastMessage_ProtocolVersion = wire::CastMessage_ProtocolVersion::CASTV2_1_0;
+pub const PROTOCOL_VERSION: wire::CastMessage_ProtocolVersion = wire::CastMessage_ProtocolVersion::CASTV2_1_0;
 
 /// The namespace a message is send over.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Namespace(pub String);
 
 /// A sender/receive ID.
 /// Examples:
 /// * `receiver-0`
 /// * `sender-0`
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EndpointName(pub String);
 
 /// A CASTV2 message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Message
 {
     /// The sender ID of the message.
@@ -33,7 +39,7 @@ pub struct Message
 }
 
 /// A message variant.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum MessageKind
 {
     /// Create a virtual connection.
@@ -45,36 +51,194 @@ pub enum MessageKind
     /// Response to a ping message
     Pong,
     /// Get the current receiver status.
-    GetStatus,
+    GetStatus {
+        request_id: RequestId,
+    },
     /// Tell the Cast device to launch an application.
     Launch {
         /// An application identifier.
         app_id: ApplicationId,
         /// A request identifier.
-        request_id: i64,
+        request_id: RequestId,
+        /// Extra options, such as the application language.
+        options: LaunchOptions,
     },
     /// Sent from the receiver to the client when an application
     /// couldn't be launched.
     LaunchError {
-        /// The textual reason why the application couldn't be launched.
-        ///
-        /// Possible values:
-        ///
-        /// * `NOT_FOUND`
-        reason: String,
-        request_id: i64,
+        /// The reason why the application couldn't be launched.
+        reason: LaunchErrorReason,
+        request_id: RequestId,
+    },
+    /// Sent from the receiver when a request could not be handled.
+    InvalidRequest {
+        reason: InvalidRequestReason,
+        request_id: RequestId,
+    },
+    /// Sent from the receiver when media could not be loaded.
+    LoadFailed {
+        request_id: RequestId,
+    },
+    /// Sent from the receiver when loading media was cancelled.
+    LoadCancelled {
+        request_id: RequestId,
     },
     /// Stop a running instance of an application.
     Stop {
         /// The ID of the session.
         session_id: SessionId,
+        request_id: RequestId,
     },
     SetVolume {
         level: Option<VolumeLevel>,
         muted: Option<bool>,
+        request_id: RequestId,
     },
     /// Tell the sender about the current receiver status.
-    ReceiverStatus(Status),
+    ///
+    /// Statuses that were not asked for have a request ID of zero.
+    ReceiverStatus {
+        status: Status,
+        request_id: RequestId,
+    },
+    /// Ask the receiver which of a set of applications it can run.
+    GetAppAvailability {
+        app_ids: Vec<ApplicationId>,
+        request_id: RequestId,
+    },
+    /// Sent from the receiver in response to `GetAppAvailability`.
+    AppAvailability {
+        availability: HashMap<ApplicationId, AppAvailability>,
+        request_id: RequestId,
+    },
+    /// A message with a type that is not understood by this crate.
+    ///
+    /// This allows messages added in newer receiver versions to be
+    /// handled by the user.
+    Unknown {
+        /// The value of the `type` field.
+        type_name: String,
+        /// The entire payload of the message.
+        payload: json::JsonValue,
+    },
+    /// Ask the receiver to describe itself.
+    GetDeviceInfo {
+        request_id: RequestId,
+    },
+    /// Sent from the receiver in response to `GetDeviceInfo`.
+    DeviceInfo {
+        description: DeviceDescription,
+        request_id: RequestId,
+    },
+    /// Ask a speaker group for its status.
+    MultizoneGetStatus {
+        request_id: RequestId,
+    },
+    /// Tell the sender about the members of a speaker group.
+    MultizoneStatus {
+        status: GroupStatus,
+        /// The ID of the request, or zero if the status was not requested.
+        request_id: RequestId,
+    },
+    /// A speaker joined the group.
+    MemberAdded(Member),
+    /// The status of a speaker in the group changed.
+    MemberUpdated(Member),
+    /// A speaker left the group.
+    MemberRemoved {
+        device_id: String,
+    },
+    /// Set the volume of a single speaker within a group.
+    MultizoneSetVolume {
+        device_id: String,
+        level: Option<VolumeLevel>,
+        muted: Option<bool>,
+        request_id: RequestId,
+    },
+    /// Ask a media application for the status of its media sessions.
+    MediaGetStatus {
+        /// The session to get the status of, or all sessions if `None`.
+        media_session_id: Option<MediaSessionId>,
+        request_id: RequestId,
+    },
+    /// Tell the sender about the status of media sessions.
+    MediaStatus {
+        statuses: Vec<MediaStatus>,
+        /// The ID of the request, or zero if the status was not requested.
+        request_id: RequestId,
+    },
+    /// Set the volume of a media stream.
+    ///
+    /// Unlike `SetVolume`, this only changes the volume of the stream
+    /// and not of the whole device.
+    MediaSetVolume {
+        media_session_id: MediaSessionId,
+        level: Option<VolumeLevel>,
+        muted: Option<bool>,
+        request_id: RequestId,
+    },
+    /// Load media into a media application.
+    MediaLoad {
+        /// Information about the media, such as `contentId`, `contentType`,
+        /// `streamType` and `duration`.
+        media: json::JsonValue,
+        /// Whether to start playing once loaded, which receivers assume
+        /// if it is not given.
+        autoplay: Option<bool>,
+        /// The position to start playing from, in seconds.
+        current_time: Option<f64>,
+        request_id: RequestId,
+    },
+    /// Resume playing paused media.
+    MediaPlay {
+        media_session_id: MediaSessionId,
+        request_id: RequestId,
+    },
+    /// Pause the media.
+    MediaPause {
+        media_session_id: MediaSessionId,
+        request_id: RequestId,
+    },
+    /// Stop playing and unload the media.
+    ///
+    /// Unlike `Stop`, this leaves the application running.
+    MediaStop {
+        media_session_id: MediaSessionId,
+        request_id: RequestId,
+    },
+    /// Change the speed media is played at.
+    MediaSetPlaybackRate {
+        media_session_id: MediaSessionId,
+        /// The new speed, where `1.0` is normal speed.
+        playback_rate: f32,
+        request_id: RequestId,
+    },
+    /// Seek to a position in the media.
+    MediaSeek {
+        media_session_id: MediaSessionId,
+        position: SeekPosition,
+        /// Whether to play or pause after seeking, or keep the current
+        /// state if `None`.
+        resume_state: Option<ResumeState>,
+        request_id: RequestId,
+    },
+    /// A message that is not interpreted by this crate.
+    ///
+    /// This is used for JSON messages on namespaces that are defined by
+    /// receiver applications, and for all binary messages.
+    Custom {
+        payload: Payload,
+    },
+}
+
+/// The payload of a message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload
+{
+    /// A UTF-8 encoded JSON payload.
+    Utf8(json::JsonValue),
+    /// A binary payload.
+    Binary(Vec<u8>),
 }
 
 impl Message
@@ -88,45 +252,15 @@ impl Message
 
     /// Creates a message from a wire message.
     pub fn from_wire_message(message: &wire::CastMessage) -> Result<Self, Error> {
-        let kind = match message.get_payload_type() {
-            wire::CastMessage_PayloadType::STRING => {
-                let data = json::parse(message.get_payload_utf8()).unwrap();
-
-                let type_name = data["type"].as_str().unwrap();
-                match type_name {
-                    "CONNECT" => MessageKind::Connect,
-                    "CLOSE" => MessageKind::Close,
-                    "PING" => MessageKind::Ping,
-                    "PONG" => MessageKind::Pong,
-                    "GET_STATUS" => MessageKind::GetStatus,
-                    "LAUNCH" => unimplemented!(),
-                    "LAUNCH_ERROR" => MessageKind::LaunchError {
-                        reason: data["reason"].as_str().unwrap().to_owned(),
-                        request_id: data["requestId"].as_i64().unwrap(),
-                    },
-                    "STOP" => {
-                        let session_id_text = data["sessionId"].as_str().unwrap();
-                        let session_id = SessionId(Uuid::parse_str(&session_id_text)?);
-                        MessageKind::Stop { session_id: session_id }
-                    },
-                    "SET_VOLUME" => unimplemented!(),
-                    "RECEIVER_STATUS" => {
-                        let status_data = &data["status"];
-                        let status = Status::from_json(&status_data)?;
-                        MessageKind::ReceiverStatus(status)
-                    },
-                    _ => return Err(ErrorKind::UnknownMessageType(type_name.to_owned()).into()),
-                }
-            },
-            wire::CastMessage_PayloadType::BINARY => {
-                return Err(ErrorKind::UnknownMessageType("binary message".to_owned()).into());
-            },
-        };
+        let namespace = Namespace(message.get_namespace().to_owned());
+
+        let payload = Payload::from_wire_message(message)?;
+        let kind = MessageKind::from_payload(&namespace, payload)?;
 
         Ok(Message {
             source: EndpointName(message.get_source_id().to_owned()),
             destination: EndpointName(message.get_destination_id().to_owned()),
-            namespace: Namespace(message.get_namespace().to_owned()),
+            namespace: namespace,
             kind: kind,
         })
     }
@@ -147,64 +281,1258 @@ impl Message
         message.set_source_id(self.source.0.clone());
         message.set_destination_id(self.destination.0.clone());
         message.set_namespace(self.namespace.0.clone());
+        self.payload().write_to(&mut message);
 
-        match self.kind {
-            MessageKind::Connect => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8("{ \"type\": \"CONNECT\" }".to_owned());
+        message
+    }
+
+    /// Gets the payload of the message.
+    pub fn payload(&self) -> Payload {
+        self.kind.to_payload()
+    }
+
+    /// Reads a message from the JSON written by `Message::to_json`.
+    pub fn from_json(data: &json::JsonValue) -> Result<Self, Error> {
+        let root = Field::root(data, "");
+        let namespace = Namespace(root.get("namespace").as_str()?.to_owned());
+        let payload = Payload::from_json(&root)?;
+
+        Ok(Message {
+            source: EndpointName(root.get("sourceId").as_str()?.to_owned()),
+            destination: EndpointName(root.get("destinationId").as_str()?.to_owned()),
+            kind: MessageKind::from_payload(&namespace, payload)?,
+            namespace: namespace,
+        })
+    }
+
+    /// Builds a JSON representation of the message.
+    ///
+    /// The fields are named after those of the wire message, and UTF-8
+    /// payloads are kept as JSON so that recorded sessions stay readable.
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut data = object! {
+            "sourceId" => &self.source.0[..],
+            "destinationId" => &self.destination.0[..],
+            "namespace" => &self.namespace.0[..]
+        };
+
+        self.payload().write_json(&mut data);
+        data
+    }
+}
+
+impl MessageKind
+{
+    /// Reads a message from its payload.
+    pub fn from_payload(namespace: &Namespace, payload: Payload) -> Result<Self, Error> {
+        match payload {
+            Payload::Utf8(data) => MessageKind::from_json(namespace, &data),
+            payload @ Payload::Binary(..) => Ok(MessageKind::Custom { payload: payload }),
+        }
+    }
+
+    /// Reads a message from its JSON payload.
+    pub fn from_json(namespace: &Namespace, data: &json::JsonValue) -> Result<Self, Error> {
+        if !namespace.is_builtin() {
+            return Ok(MessageKind::Custom { payload: Payload::Utf8(data.clone()) });
+        }
+
+        let payload = Field::root(data, "");
+
+        // Some responses identify themselves with `responseType` instead of `type`.
+        if !payload.get("type").is_present() {
+            if let Some(response_type) = payload.get("responseType").optional(Field::as_str)? {
+                return MessageKind::from_response_json(response_type, &payload);
+            }
+        }
+
+        let type_name = payload.get("type").as_str()?;
+
+        // The multizone namespace reuses the names of receiver messages.
+        if *namespace == ::namespace::multizone() {
+            return MessageKind::from_multizone_json(type_name, &payload);
+        }
+
+        // The media namespace also uses the shared error responses below.
+        if *namespace == ::namespace::media() {
+            if let Some(kind) = MessageKind::from_media_json(type_name, &payload)? {
+                return Ok(kind);
+            }
+        }
+
+        Ok(match type_name {
+            "CONNECT" => MessageKind::Connect,
+            "CLOSE" => MessageKind::Close,
+            "PING" => MessageKind::Ping,
+            "PONG" => MessageKind::Pong,
+            "GET_STATUS" => MessageKind::GetStatus {
+                request_id: payload.get("requestId").as_i64()?,
             },
-            MessageKind::Close => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8("{ \"type\": \"CLOSE\" }".to_owned());
+            "LAUNCH" => MessageKind::Launch {
+                app_id: ApplicationId(payload.get("appId").as_str()?.to_owned()),
+                request_id: payload.get("requestId").as_i64()?,
+                options: LaunchOptions::from_field(&payload)?,
             },
-            MessageKind::Ping => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8("{\"type\":\"PING\"}".to_owned());
+            "LAUNCH_ERROR" => MessageKind::LaunchError {
+                reason: LaunchErrorReason::from(payload.get("reason").as_str()?),
+                request_id: payload.get("requestId").as_i64()?,
             },
-            MessageKind::Pong => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8("{\"type\":\"PONG\"}".to_owned());
+            "INVALID_REQUEST" => MessageKind::InvalidRequest {
+                reason: InvalidRequestReason::from(payload.get("reason").as_str()?),
+                request_id: payload.get("requestId").as_i64()?,
             },
-            MessageKind::GetStatus => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8("{ \"type\": \"GET_STATUS\" }".to_owned());
+            "LOAD_FAILED" => MessageKind::LoadFailed {
+                request_id: payload.get("requestId").as_i64()?,
             },
-            MessageKind::Launch { ref app_id, request_id } => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8(json::stringify(object! {
+            "LOAD_CANCELLED" => MessageKind::LoadCancelled {
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "STOP" => MessageKind::Stop {
+                session_id: SessionId(payload.get("sessionId").as_uuid()?),
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "SET_VOLUME" => {
+                let volume = payload.get("volume");
+
+                MessageKind::SetVolume {
+                    level: volume.get("level").optional(Field::as_f32)?.map(VolumeLevel),
+                    muted: volume.get("muted").optional(Field::as_bool)?,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            "RECEIVER_STATUS" => MessageKind::ReceiverStatus {
+                status: Status::from_field(&payload.get("status"))?,
+                request_id: payload.get("requestId").optional(Field::as_i64)?.unwrap_or(0),
+            },
+            "GET_APP_AVAILABILITY" => {
+                let app_ids: Result<Vec<_>, Error> = payload.get("appId").as_array()?.iter().map(|app_id| {
+                    Ok(ApplicationId(app_id.as_str()?.to_owned()))
+                }).collect();
+
+                MessageKind::GetAppAvailability {
+                    app_ids: app_ids?,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            "GET_DEVICE_INFO" => MessageKind::GetDeviceInfo {
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "DEVICE_INFO" => MessageKind::DeviceInfo {
+                description: DeviceDescription::from_field(&payload)?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            _ => MessageKind::Unknown {
+                type_name: type_name.to_owned(),
+                payload: data.clone(),
+            },
+        })
+    }
+
+    /// Reads a message on the multizone namespace.
+    fn from_multizone_json(type_name: &str, payload: &Field) -> Result<Self, Error> {
+        Ok(match type_name {
+            "GET_STATUS" => MessageKind::MultizoneGetStatus {
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "MULTIZONE_STATUS" => MessageKind::MultizoneStatus {
+                status: GroupStatus::from_field(&payload.get("status"))?,
+                request_id: payload.get("requestId").optional(Field::as_i64)?.unwrap_or(0),
+            },
+            "DEVICE_ADDED" => MessageKind::MemberAdded(Member::from_field(&payload.get("device"))?),
+            "DEVICE_UPDATED" => MessageKind::MemberUpdated(Member::from_field(&payload.get("device"))?),
+            "DEVICE_REMOVED" => MessageKind::MemberRemoved {
+                device_id: payload.get("deviceId").as_str()?.to_owned(),
+            },
+            "SET_VOLUME" => {
+                let volume = payload.get("volume");
+
+                MessageKind::MultizoneSetVolume {
+                    device_id: payload.get("deviceId").as_str()?.to_owned(),
+                    level: volume.get("level").optional(Field::as_f32)?.map(VolumeLevel),
+                    muted: volume.get("muted").optional(Field::as_bool)?,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            _ => MessageKind::Unknown {
+                type_name: type_name.to_owned(),
+                payload: payload.value.clone(),
+            },
+        })
+    }
+
+    /// Reads a message that is specific to the media namespace.
+    fn from_media_json(type_name: &str, payload: &Field) -> Result<Option<Self>, Error> {
+        Ok(Some(match type_name {
+            "GET_STATUS" => MessageKind::MediaGetStatus {
+                media_session_id: payload.get("mediaSessionId").optional(Field::as_i64)?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "MEDIA_STATUS" => {
+                let statuses: Result<Vec<_>, _> = payload.get("status").as_array()?.iter().map(MediaStatus::from_field).collect();
+
+                MessageKind::MediaStatus {
+                    statuses: statuses?,
+                    request_id: payload.get("requestId").optional(Field::as_i64)?.unwrap_or(0),
+                }
+            },
+            "SET_VOLUME" => {
+                let volume = payload.get("volume");
+
+                MessageKind::MediaSetVolume {
+                    media_session_id: payload.get("mediaSessionId").as_i64()?,
+                    level: volume.get("level").optional(Field::as_f32)?.map(VolumeLevel),
+                    muted: volume.get("muted").optional(Field::as_bool)?,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            "LOAD" => {
+                let media = payload.get("media");
+                if !media.value.is_object() {
+                    return Err(ErrorKind::InvalidFieldType(media.path.clone(), "an object").into());
+                }
+
+                MessageKind::MediaLoad {
+                    media: media.value.clone(),
+                    autoplay: payload.get("autoplay").optional(Field::as_bool)?,
+                    current_time: payload.get("currentTime").optional(Field::as_f64)?,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            "PLAY" => MessageKind::MediaPlay {
+                media_session_id: payload.get("mediaSessionId").as_i64()?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "PAUSE" => MessageKind::MediaPause {
+                media_session_id: payload.get("mediaSessionId").as_i64()?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "STOP" => MessageKind::MediaStop {
+                media_session_id: payload.get("mediaSessionId").as_i64()?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "SET_PLAYBACK_RATE" => MessageKind::MediaSetPlaybackRate {
+                media_session_id: payload.get("mediaSessionId").as_i64()?,
+                playback_rate: payload.get("playbackRate").as_f32()?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            "SEEK" => MessageKind::MediaSeek {
+                media_session_id: payload.get("mediaSessionId").as_i64()?,
+                position: SeekPosition::from_field(payload)?,
+                resume_state: payload.get("resumeState").optional(ResumeState::from_field)?,
+                request_id: payload.get("requestId").as_i64()?,
+            },
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Reads a message that is identified by its `responseType`.
+    fn from_response_json(response_type: &str, payload: &Field) -> Result<Self, Error> {
+        Ok(match response_type {
+            "GET_APP_AVAILABILITY" => {
+                let availability_field = payload.get("availability");
+                if !availability_field.value.is_object() {
+                    return Err(ErrorKind::InvalidFieldType(availability_field.path.clone(), "an object").into());
+                }
+
+                let mut availability = HashMap::new();
+                for (app_id, _) in availability_field.value.entries() {
+                    let field = availability_field.get(app_id);
+
+                    let app_availability = match field.as_str()? {
+                        "APP_AVAILABLE" => AppAvailability::Available,
+                        "APP_UNAVAILABLE" => AppAvailability::Unavailable,
+                        other => return Err(ErrorKind::InvalidFieldValue(field.path.clone(), other.to_owned()).into()),
+                    };
+
+                    availability.insert(ApplicationId(app_id.to_owned()), app_availability);
+                }
+
+                MessageKind::AppAvailability {
+                    availability: availability,
+                    request_id: payload.get("requestId").as_i64()?,
+                }
+            },
+            _ => MessageKind::Unknown {
+                type_name: response_type.to_owned(),
+                payload: payload.value.clone(),
+            },
+        })
+    }
+
+    /// Gets the error if this message is an error response to a request.
+    pub fn request_error(&self) -> Option<(RequestId, RequestError)> {
+        match *self {
+            MessageKind::LaunchError { ref reason, request_id } => Some((request_id, RequestError::LaunchFailed(reason.clone()))),
+            MessageKind::InvalidRequest { ref reason, request_id } => Some((request_id, RequestError::InvalidRequest(reason.clone()))),
+            MessageKind::LoadFailed { request_id } => Some((request_id, RequestError::LoadFailed)),
+            MessageKind::LoadCancelled { request_id } => Some((request_id, RequestError::LoadCancelled)),
+            _ => None,
+        }
+    }
+
+    /// Builds the payload of the message.
+    pub fn to_payload(&self) -> Payload {
+        let data = match *self {
+            MessageKind::Connect => object! { "type" => "CONNECT" },
+            MessageKind::Close => object! { "type" => "CLOSE" },
+            MessageKind::Ping => object! { "type" => "PING" },
+            MessageKind::Pong => object! { "type" => "PONG" },
+            MessageKind::GetStatus { request_id } => object! {
+                "type" => "GET_STATUS",
+                "requestId" => request_id
+            },
+            MessageKind::Launch { ref app_id, request_id, ref options } => {
+                let mut data = object! {
                     "type" => "LAUNCH",
                     "appId" => &app_id.0[..],
                     "requestId" => request_id
-                }));
+                };
+
+                options.write_to(&mut data);
+                data
             },
-            MessageKind::LaunchError { .. } => unimplemented!(),
-            MessageKind::Stop { session_id } => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                message.set_payload_utf8(json::stringify(object! {
-                    "type" => "STOP",
-                    "sessionId" => session_id.0.to_string()
-                }));
+            MessageKind::LaunchError { ref reason, request_id } => object! {
+                "type" => "LAUNCH_ERROR",
+                "reason" => reason.as_str(),
+                "requestId" => request_id
             },
-            MessageKind::SetVolume { level, muted } => {
-                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
-                let mut volume = object! { };
+            MessageKind::InvalidRequest { ref reason, request_id } => object! {
+                "type" => "INVALID_REQUEST",
+                "reason" => reason.as_str(),
+                "requestId" => request_id
+            },
+            MessageKind::LoadFailed { request_id } => object! {
+                "type" => "LOAD_FAILED",
+                "requestId" => request_id
+            },
+            MessageKind::LoadCancelled { request_id } => object! {
+                "type" => "LOAD_CANCELLED",
+                "requestId" => request_id
+            },
+            MessageKind::Stop { session_id, request_id } => object! {
+                "type" => "STOP",
+                "sessionId" => session_id.0.to_string(),
+                "requestId" => request_id
+            },
+            MessageKind::SetVolume { level, muted, request_id } => object! {
+                "type" => "SET_VOLUME",
+                "volume" => volume_json(level, muted),
+                "requestId" => request_id
+            },
+            MessageKind::ReceiverStatus { ref status, request_id } => object! {
+                "type" => "RECEIVER_STATUS",
+                "status" => status.to_json(),
+                "requestId" => request_id
+            },
+            MessageKind::GetAppAvailability { ref app_ids, request_id } => {
+                let app_ids: Vec<_> = app_ids.iter().map(|app_id| app_id.0.clone()).collect();
 
-                if let Some(level) = level { volume["level"] = level.0.into() };
-                if let Some(muted) = muted { volume["muted"] = muted.into() };
+                object! {
+                    "type" => "GET_APP_AVAILABILITY",
+                    "appId" => app_ids,
+                    "requestId" => request_id
+                }
+            },
+            MessageKind::AppAvailability { ref availability, request_id } => {
+                let mut availability_data = object! { };
 
-                message.set_payload_utf8(json::stringify(object! {
-                    "type" => "SET_VOLUME",
-                    "volume" => volume
-                }));
+                for (app_id, app_availability) in availability.iter() {
+                    availability_data[&app_id.0[..]] = match *app_availability {
+                        AppAvailability::Available => "APP_AVAILABLE",
+                        AppAvailability::Unavailable => "APP_UNAVAILABLE",
+                    }.into();
+                }
+
+                object! {
+                    "responseType" => "GET_APP_AVAILABILITY",
+                    "availability" => availability_data,
+                    "requestId" => request_id
+                }
+            },
+            MessageKind::GetDeviceInfo { request_id } => object! {
+                "type" => "GET_DEVICE_INFO",
+                "requestId" => request_id,
+                "data" => object! { }
+            },
+            MessageKind::DeviceInfo { ref description, request_id } => {
+                let mut data = object! {
+                    "type" => "DEVICE_INFO",
+                    "requestId" => request_id
+                };
+
+                description.write_to(&mut data);
+                data
+            },
+            MessageKind::MultizoneGetStatus { request_id } => object! {
+                "type" => "GET_STATUS",
+                "requestId" => request_id
+            },
+            MessageKind::MultizoneStatus { ref status, request_id } => object! {
+                "type" => "MULTIZONE_STATUS",
+                "status" => status.to_json(),
+                "requestId" => request_id
+            },
+            MessageKind::MemberAdded(ref member) => object! {
+                "type" => "DEVICE_ADDED",
+                "device" => member.to_json()
+            },
+            MessageKind::MemberUpdated(ref member) => object! {
+                "type" => "DEVICE_UPDATED",
+                "device" => member.to_json()
+            },
+            MessageKind::MemberRemoved { ref device_id } => object! {
+                "type" => "DEVICE_REMOVED",
+                "deviceId" => &device_id[..]
+            },
+            MessageKind::MultizoneSetVolume { ref device_id, level, muted, request_id } => object! {
+                "type" => "SET_VOLUME",
+                "deviceId" => &device_id[..],
+                "volume" => volume_json(level, muted),
+                "requestId" => request_id
+            },
+            MessageKind::MediaGetStatus { media_session_id, request_id } => {
+                let mut data = object! {
+                    "type" => "GET_STATUS",
+                    "requestId" => request_id
+                };
+
+                if let Some(media_session_id) = media_session_id { data["mediaSessionId"] = media_session_id.into() };
+                data
+            },
+            MessageKind::MediaStatus { ref statuses, request_id } => {
+                let statuses: Vec<_> = statuses.iter().map(MediaStatus::to_json).collect();
+
+                object! {
+                    "type" => "MEDIA_STATUS",
+                    "status" => statuses,
+                    "requestId" => request_id
+                }
+            },
+            MessageKind::MediaSetVolume { media_session_id, level, muted, request_id } => object! {
+                "type" => "SET_VOLUME",
+                "mediaSessionId" => media_session_id,
+                "volume" => volume_json(level, muted),
+                "requestId" => request_id
+            },
+            MessageKind::MediaLoad { ref media, autoplay, current_time, request_id } => {
+                let mut data = object! {
+                    "type" => "LOAD",
+                    "media" => media.clone(),
+                    "requestId" => request_id
+                };
+
+                if let Some(autoplay) = autoplay { data["autoplay"] = autoplay.into() };
+                if let Some(current_time) = current_time { data["currentTime"] = current_time.into() };
+                data
+            },
+            MessageKind::MediaPlay { media_session_id, request_id } => object! {
+                "type" => "PLAY",
+                "mediaSessionId" => media_session_id,
+                "requestId" => request_id
+            },
+            MessageKind::MediaPause { media_session_id, request_id } => object! {
+                "type" => "PAUSE",
+                "mediaSessionId" => media_session_id,
+                "requestId" => request_id
+            },
+            MessageKind::MediaStop { media_session_id, request_id } => object! {
+                "type" => "STOP",
+                "mediaSessionId" => media_session_id,
+                "requestId" => request_id
+            },
+            MessageKind::MediaSetPlaybackRate { media_session_id, playback_rate, request_id } => object! {
+                "type" => "SET_PLAYBACK_RATE",
+                "mediaSessionId" => media_session_id,
+                "playbackRate" => playback_rate,
+                "requestId" => request_id
+            },
+            MessageKind::MediaSeek { media_session_id, position, resume_state, request_id } => {
+                let mut data = object! {
+                    "type" => "SEEK",
+                    "mediaSessionId" => media_session_id,
+                    "requestId" => request_id
+                };
+
+                position.write_to(&mut data);
+                if let Some(resume_state) = resume_state { data["resumeState"] = resume_state.as_str().into() };
+                data
+            },
+            MessageKind::Unknown { ref type_name, ref payload } => {
+                let mut payload = payload.clone();
+                payload["type"] = type_name.clone().into();
+                payload
+            },
+            MessageKind::Custom { ref payload } => return payload.clone(),
+        };
+
+        Payload::Utf8(data)
+    }
+}
+
+/// Builds the `volume` object of a `SET_VOLUME` message.
+fn volume_json(level: Option<VolumeLevel>, muted: Option<bool>) -> json::JsonValue {
+    let mut volume = object! { };
+
+    if let Some(level) = level { volume["level"] = level.0.into() };
+    if let Some(muted) = muted { volume["muted"] = muted.into() };
+
+    volume
+}
+
+impl Payload
+{
+    /// Reads the payload out of a wire message.
+    pub fn from_wire_message(message: &wire::CastMessage) -> Result<Self, Error> {
+        match message.get_payload_type() {
+            wire::CastMessage_PayloadType::STRING => {
+                let data = json::parse(message.get_payload_utf8())?;
+                Ok(Payload::Utf8(data))
+            },
+            wire::CastMessage_PayloadType::BINARY => {
+                Ok(Payload::Binary(message.get_payload_binary().to_owned()))
             },
-            MessageKind::ReceiverStatus(..) => unimplemented!(),
         }
+    }
 
-        message
+    /// Writes the payload into a wire message.
+    pub fn write_to(&self, message: &mut wire::CastMessage) {
+        match *self {
+            Payload::Utf8(ref data) => {
+                message.set_payload_type(wire::CastMessage_PayloadType::STRING);
+                message.set_payload_utf8(json::stringify(data.clone()));
+            },
+            Payload::Binary(ref bytes) => {
+                message.set_payload_type(wire::CastMessage_PayloadType::BINARY);
+                message.set_payload_binary(bytes.clone());
+            },
+        }
+    }
+
+    /// Reads the payload out of the JSON written by `Message::to_json`.
+    pub fn from_json(message: &Field) -> Result<Self, Error> {
+        let binary = message.get("payloadBinary");
+
+        if binary.is_present() {
+            Ok(Payload::Binary(binary.as_bytes()?))
+        } else {
+            let payload = message.get("payloadUtf8");
+            if !payload.is_present() {
+                return Err(ErrorKind::MissingField(payload.path).into());
+            }
+
+            Ok(Payload::Utf8(payload.value.clone()))
+        }
+    }
+
+    /// Writes the payload into the JSON representation of a message.
+    pub fn write_json(&self, message: &mut json::JsonValue) {
+        match *self {
+            Payload::Utf8(ref data) => message["payloadUtf8"] = data.clone(),
+            Payload::Binary(ref bytes) => message["payloadBinary"] = bytes.clone().into(),
+        }
+    }
+
+    /// Gets the JSON data if this is a UTF-8 payload.
+    pub fn as_json(&self) -> Option<&json::JsonValue> {
+        match *self {
+            Payload::Utf8(ref data) => Some(data),
+            Payload::Binary(..) => None,
+        }
+    }
+
+    /// Gets the bytes if this is a binary payload.
+    pub fn as_binary(&self) -> Option<&[u8]> {
+        match *self {
+            Payload::Utf8(..) => None,
+            Payload::Binary(ref bytes) => Some(bytes),
+        }
+    }
+}
+
+impl Namespace
+{
+    /// Checks whether messages on this namespace are interpreted by this crate.
+    pub fn is_builtin(&self) -> bool {
+        ::namespace::builtin().contains(&self.0.as_str())
     }
 }
 
 impl EndpointName
 {
     pub fn is_broadcast(&self) -> bool { self.0 == "*" }
+
+    /// Gets the endpoint name that addresses every sender.
+    pub fn broadcast() -> Self { EndpointName("*".to_owned()) }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use super::PROTOCOL_VERSION;
+    use {Status, ApplicationId, AppAvailability, RequestId, SessionId, VolumeLevel, Error, ErrorKind};
+    use status::{Volume, Application};
+    use launch::{LaunchOptions, AppType, Credentials};
+    use request_error::{RequestError, InvalidRequestReason, LaunchErrorReason};
+    use device_info::DeviceDescription;
+    use multizone::{GroupStatus, Member, MemberVolume};
+    use media::{MediaStatus, MediaVolume, PlayerState, LiveSeekableRange, SeekPosition, ResumeState};
+
+    use std::collections::HashMap;
+
+    use quickcheck::{Arbitrary, Gen};
+    use uuid::Uuid;
+    use wire;
+
+    /// Levels are kept to multiples of 1/64, which survive being written
+    /// to JSON. The json crate loses precision on most other fractions.
+    fn arbitrary_level<G: Gen>(g: &mut G) -> VolumeLevel {
+        VolumeLevel(g.gen_range(0, 65) as f32 / 64.0)
+    }
+
+    fn arbitrary_request_id<G: Gen>(g: &mut G) -> RequestId {
+        g.gen::<u32>() as i64
+    }
+
+    fn arbitrary_uuid<G: Gen>(g: &mut G) -> Uuid {
+        let bytes: Vec<u8> = (0..16).map(|_| g.gen()).collect();
+        Uuid::from_bytes(&bytes).unwrap()
+    }
+
+    fn arbitrary_option<T, G, F>(g: &mut G, f: F) -> Option<T>
+        where G: Gen, F: FnOnce(&mut G) -> T {
+        if g.gen() { Some(f(g)) } else { None }
+    }
+
+    fn arbitrary_launch_options<G: Gen>(g: &mut G) -> LaunchOptions {
+        let app_types = [AppType::Web, AppType::AndroidTv, AppType::Other("FUTURE_APP_TYPE".to_owned())];
+        let app_type_count = g.gen_range(0, app_types.len() + 1);
+
+        LaunchOptions {
+            language: arbitrary_option(g, String::arbitrary),
+            supported_app_types: app_types[..app_type_count].to_owned(),
+            app_params: arbitrary_option(g, |g| object! { "text" => String::arbitrary(g) }),
+            credentials: arbitrary_option(g, |g| Credentials {
+                credentials: String::arbitrary(g),
+                credentials_type: arbitrary_option(g, String::arbitrary),
+            }),
+        }
+    }
+
+    fn arbitrary_member<G: Gen>(g: &mut G) -> Member {
+        Member {
+            device_id: String::arbitrary(g),
+            name: String::arbitrary(g),
+            capabilities: arbitrary_option(g, |g| g.gen_range(0, 64)),
+            volume: MemberVolume {
+                level: arbitrary_level(g),
+                muted: g.gen(),
+            },
+        }
+    }
+
+    fn arbitrary_media_status<G: Gen>(g: &mut G) -> MediaStatus {
+        let player_states = [PlayerState::Idle, PlayerState::Playing, PlayerState::Paused,
+                             PlayerState::Buffering, PlayerState::Other("FUTURE_STATE".to_owned())];
+
+        MediaStatus {
+            media_session_id: arbitrary_request_id(g),
+            player_state: g.choose(&player_states).unwrap().clone(),
+            idle_reason: arbitrary_option(g, String::arbitrary),
+            current_time: arbitrary_time(g),
+            playback_rate: *g.choose(&[0.5, 1.0, 1.5, 2.0]).unwrap(),
+            volume: MediaVolume {
+                level: arbitrary_option(g, arbitrary_level),
+                muted: arbitrary_option(g, |g| g.gen()),
+            },
+            supported_media_commands: g.gen_range(0, 1 << 16),
+            media: arbitrary_option(g, |g| object! { "contentId" => String::arbitrary(g) }),
+            live_seekable_range: arbitrary_option(g, |g| LiveSeekableRange {
+                start: arbitrary_option(g, arbitrary_time),
+                end: arbitrary_option(g, arbitrary_time),
+                is_moving_window: arbitrary_option(g, |g| g.gen()),
+                is_live_done: arbitrary_option(g, |g| g.gen()),
+            }),
+        }
+    }
+
+    fn arbitrary_time<G: Gen>(g: &mut G) -> f64 {
+        g.gen_range(0, 40000) as f64 / 4.0
+    }
+
+    impl Arbitrary for Message
+    {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let kind = MessageKind::arbitrary(g);
+
+            // Only JSON messages on builtin namespaces are decoded into
+            // specific message kinds.
+            let namespace = match kind {
+                MessageKind::Custom { .. } => Namespace(format!("urn:x-cast:com.example.{}", String::arbitrary(g))),
+                MessageKind::MultizoneGetStatus { .. } |
+                    MessageKind::MultizoneStatus { .. } |
+                    MessageKind::MemberAdded(..) |
+                    MessageKind::MemberUpdated(..) |
+                    MessageKind::MemberRemoved { .. } |
+                    MessageKind::MultizoneSetVolume { .. } => ::namespace::multizone(),
+                MessageKind::MediaGetStatus { .. } |
+                    MessageKind::MediaStatus { .. } |
+                    MessageKind::MediaSetVolume { .. } |
+                    MessageKind::MediaLoad { .. } |
+                    MessageKind::MediaPlay { .. } |
+                    MessageKind::MediaPause { .. } |
+                    MessageKind::MediaStop { .. } |
+                    MessageKind::MediaSetPlaybackRate { .. } |
+                    MessageKind::MediaSeek { .. } => ::namespace::media(),
+                _ => ::namespace::receiver(),
+            };
+
+            Message {
+                source: EndpointName(String::arbitrary(g)),
+                destination: EndpointName(String::arbitrary(g)),
+                namespace: namespace,
+                kind: kind,
+            }
+        }
+    }
+
+    impl Arbitrary for MessageKind
+    {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            match g.gen_range(0, 34) {
+                0 => MessageKind::Connect,
+                1 => MessageKind::Close,
+                2 => MessageKind::Ping,
+                3 => MessageKind::Pong,
+                4 => MessageKind::GetStatus { request_id: arbitrary_request_id(g) },
+                5 => MessageKind::Launch {
+                    app_id: ApplicationId(String::arbitrary(g)),
+                    request_id: arbitrary_request_id(g),
+                    options: arbitrary_launch_options(g),
+                },
+                6 => {
+                    let reasons = [LaunchErrorReason::NotFound, LaunchErrorReason::Cancelled,
+                                   LaunchErrorReason::Other("FUTURE_REASON".to_owned())];
+
+                    MessageKind::LaunchError {
+                        reason: g.choose(&reasons).unwrap().clone(),
+                        request_id: arbitrary_request_id(g),
+                    }
+                },
+                7 => MessageKind::Stop {
+                    session_id: SessionId(arbitrary_uuid(g)),
+                    request_id: arbitrary_request_id(g),
+                },
+                8 => MessageKind::SetVolume {
+                    level: arbitrary_option(g, arbitrary_level),
+                    muted: arbitrary_option(g, |g| g.gen()),
+                    request_id: arbitrary_request_id(g),
+                },
+                9 => MessageKind::ReceiverStatus {
+                    status: Status::arbitrary(g),
+                    request_id: arbitrary_request_id(g),
+                },
+                10 => {
+                    let type_name = format!("UNKNOWN_{}", g.gen::<u32>());
+
+                    MessageKind::Unknown {
+                        type_name: type_name.clone(),
+                        payload: object! {
+                            "type" => type_name,
+                            "requestId" => arbitrary_request_id(g),
+                            "text" => String::arbitrary(g)
+                        },
+                    }
+                },
+                11 => {
+                    let payload = if g.gen() {
+                        Payload::Utf8(object! {
+                            "text" => String::arbitrary(g),
+                            "count" => g.gen::<u32>()
+                        })
+                    } else {
+                        Payload::Binary(Vec::arbitrary(g))
+                    };
+
+                    MessageKind::Custom { payload: payload }
+                },
+                12 => MessageKind::GetAppAvailability {
+                    app_ids: Vec::<String>::arbitrary(g).into_iter().map(ApplicationId).collect(),
+                    request_id: arbitrary_request_id(g),
+                },
+                13 => MessageKind::AppAvailability {
+                    availability: Vec::<(String, bool)>::arbitrary(g).into_iter().map(|(app_id, available)| {
+                        let availability = if available { AppAvailability::Available } else { AppAvailability::Unavailable };
+                        (ApplicationId(app_id), availability)
+                    }).collect(),
+                    request_id: arbitrary_request_id(g),
+                },
+                14 => {
+                    let reasons = [InvalidRequestReason::InvalidCommand, InvalidRequestReason::DuplicateRequestId,
+                                   InvalidRequestReason::Other("FUTURE_REASON".to_owned())];
+
+                    MessageKind::InvalidRequest {
+                        reason: g.choose(&reasons).unwrap().clone(),
+                        request_id: arbitrary_request_id(g),
+                    }
+                },
+                15 => MessageKind::LoadFailed { request_id: arbitrary_request_id(g) },
+                16 => MessageKind::LoadCancelled { request_id: arbitrary_request_id(g) },
+                17 => MessageKind::GetDeviceInfo { request_id: arbitrary_request_id(g) },
+                18 => MessageKind::DeviceInfo {
+                    description: DeviceDescription {
+                        device_id: arbitrary_uuid(g).simple().to_string(),
+                        friendly_name: String::arbitrary(g),
+                        model: arbitrary_option(g, String::arbitrary),
+                        capabilities: arbitrary_option(g, |g| g.gen_range(0, 64)),
+                        control_notifications: arbitrary_option(g, |g| g.gen_range(0, 2)),
+                        icon_url: arbitrary_option(g, String::arbitrary),
+                        receiver_metrics_id: arbitrary_option(g, String::arbitrary),
+                    },
+                    request_id: arbitrary_request_id(g),
+                },
+                19 => MessageKind::MultizoneGetStatus { request_id: arbitrary_request_id(g) },
+                20 => {
+                    let member_count = g.gen_range(0, 4);
+
+                    MessageKind::MultizoneStatus {
+                        status: GroupStatus {
+                            members: (0..member_count).map(|_| arbitrary_member(g)).collect(),
+                            is_multichannel: arbitrary_option(g, |g| g.gen()),
+                        },
+                        request_id: arbitrary_request_id(g),
+                    }
+                },
+                21 => MessageKind::MemberAdded(arbitrary_member(g)),
+                22 => MessageKind::MemberUpdated(arbitrary_member(g)),
+                23 => MessageKind::MemberRemoved { device_id: String::arbitrary(g) },
+                24 => MessageKind::MultizoneSetVolume {
+                    device_id: String::arbitrary(g),
+                    level: arbitrary_option(g, arbitrary_level),
+                    muted: arbitrary_option(g, |g| g.gen()),
+                    request_id: arbitrary_request_id(g),
+                },
+                25 => MessageKind::MediaGetStatus {
+                    media_session_id: arbitrary_option(g, arbitrary_request_id),
+                    request_id: arbitrary_request_id(g),
+                },
+                26 => {
+                    let status_count = g.gen_range(0, 3);
+
+                    MessageKind::MediaStatus {
+                        statuses: (0..status_count).map(|_| arbitrary_media_status(g)).collect(),
+                        request_id: arbitrary_request_id(g),
+                    }
+                },
+                27 => MessageKind::MediaSetVolume {
+                    media_session_id: arbitrary_request_id(g),
+                    level: arbitrary_option(g, arbitrary_level),
+                    muted: arbitrary_option(g, |g| g.gen()),
+                    request_id: arbitrary_request_id(g),
+                },
+                28 => MessageKind::MediaSetPlaybackRate {
+                    media_session_id: arbitrary_request_id(g),
+                    playback_rate: *g.choose(&[0.5, 1.0, 1.5, 2.0]).unwrap(),
+                    request_id: arbitrary_request_id(g),
+                },
+                29 => {
+                    let position = if g.gen() {
+                        SeekPosition::Absolute(arbitrary_time(g))
+                    } else {
+                        SeekPosition::Relative(arbitrary_time(g) - 5000.0)
+                    };
+
+                    MessageKind::MediaSeek {
+                        media_session_id: arbitrary_request_id(g),
+                        position: position,
+                        resume_state: *g.choose(&[None, Some(ResumeState::PlaybackStart), Some(ResumeState::PlaybackPause)]).unwrap(),
+                        request_id: arbitrary_request_id(g),
+                    }
+                },
+                30 => MessageKind::MediaLoad {
+                    media: object! {
+                        "contentId" => String::arbitrary(g),
+                        "duration" => arbitrary_time(g)
+                    },
+                    autoplay: arbitrary_option(g, |g| g.gen()),
+                    current_time: arbitrary_option(g, arbitrary_time),
+                    request_id: arbitrary_request_id(g),
+                },
+                31 => MessageKind::MediaPlay { media_session_id: arbitrary_request_id(g), request_id: arbitrary_request_id(g) },
+                32 => MessageKind::MediaPause { media_session_id: arbitrary_request_id(g), request_id: arbitrary_request_id(g) },
+                33 => MessageKind::MediaStop { media_session_id: arbitrary_request_id(g), request_id: arbitrary_request_id(g) },
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    impl Arbitrary for Status
+    {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let application_count = g.gen_range(0, 3);
+
+            Status {
+                volume: Volume {
+                    control_type: String::arbitrary(g),
+                    level: arbitrary_level(g),
+                    muted: g.gen(),
+                    step_interval: arbitrary_level(g),
+                },
+                is_active_input: arbitrary_option(g, |g| g.gen()),
+                is_stand_by: arbitrary_option(g, |g| g.gen()),
+                user_eq: None,
+                applications: (0..application_count).map(|_| Application {
+                    id: ApplicationId(String::arbitrary(g)),
+                    display_name: String::arbitrary(g),
+                    is_idle_screen: g.gen(),
+                    session_id: arbitrary_uuid(g),
+                    status_text: String::arbitrary(g),
+                    app_type: arbitrary_option(g, String::arbitrary),
+                    icon_url: arbitrary_option(g, String::arbitrary),
+                    universal_app_id: arbitrary_option(g, String::arbitrary),
+                    launched_from_cloud: arbitrary_option(g, |g| g.gen()),
+                    transport_id: arbitrary_option(g, |g| EndpointName(String::arbitrary(g))),
+                    namespaces: Vec::<String>::arbitrary(g).into_iter().map(Namespace).collect(),
+                }).collect(),
+            }
+        }
+    }
+
+    fn parse_payload(payload: &str) -> Result<Message, Error> {
+        parse_payload_on(::namespace::receiver(), payload)
+    }
+
+    fn parse_payload_on(namespace: Namespace, payload: &str) -> Result<Message, Error> {
+        let mut message = wire::CastMessage::new();
+        message.set_protocol_version(PROTOCOL_VERSION);
+        message.set_source_id("receiver-0".to_owned());
+        message.set_destination_id("sender-0".to_owned());
+        message.set_namespace(namespace.0);
+        message.set_payload_type(wire::CastMessage_PayloadType::STRING);
+        message.set_payload_utf8(payload.to_owned());
+
+        Message::from_wire_message(&message)
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        assert!(parse_payload("{ \"type\": ").is_err());
+    }
+
+    #[test]
+    fn missing_type_names_the_field() {
+        match parse_payload("{ \"requestId\": 2 }").unwrap_err().kind() {
+            &ErrorKind::MissingField(ref path) => assert_eq!(path, "type"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn mistyped_request_id_names_the_field() {
+        match parse_payload("{ \"type\": \"LAUNCH\", \"appId\": \"YouTube\", \"requestId\": \"2\" }").unwrap_err().kind() {
+            &ErrorKind::InvalidFieldType(ref path, _) => assert_eq!(path, "requestId"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unknown_types_keep_their_payload() {
+        let message = parse_payload("{ \"type\": \"FUTURE_THING\", \"answer\": 42 }").unwrap();
+
+        assert_eq!(message.kind, MessageKind::Unknown {
+            type_name: "FUTURE_THING".to_owned(),
+            payload: object! { "type" => "FUTURE_THING", "answer" => 42 },
+        });
+    }
+
+    #[test]
+    fn custom_namespaces_do_not_need_a_type() {
+        let namespace = Namespace("urn:x-cast:com.example.player".to_owned());
+        let message = parse_payload_on(namespace, "{ \"track\": 3 }").unwrap();
+
+        assert_eq!(message.kind, MessageKind::Custom {
+            payload: Payload::Utf8(object! { "track" => 3 }),
+        });
+    }
+
+    #[test]
+    fn binary_payloads_use_the_binary_field() {
+        let message = Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::namespace::device_auth(),
+            kind: MessageKind::Custom { payload: Payload::Binary(vec![1, 2, 3]) },
+        };
+
+        let wire_message = message.as_wire_message();
+        assert_eq!(wire_message.get_payload_type(), wire::CastMessage_PayloadType::BINARY);
+        assert_eq!(wire_message.get_payload_binary(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn binary_payloads_are_written_as_json_arrays() {
+        let message = Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::namespace::device_auth(),
+            kind: MessageKind::Custom { payload: Payload::Binary(vec![1, 2, 3]) },
+        };
+
+        assert_eq!(message.to_json()["payloadBinary"], array![1, 2, 3]);
+        assert_eq!(Message::from_json(&message.to_json()).unwrap(), message);
+    }
+
+    #[test]
+    fn json_binary_payloads_must_be_bytes() {
+        let data = object! {
+            "sourceId" => "sender-0",
+            "destinationId" => "receiver-0",
+            "namespace" => "urn:x-cast:com.example.binary",
+            "payloadBinary" => array![1, 256]
+        };
+
+        match Message::from_json(&data).unwrap_err().kind() {
+            &ErrorKind::InvalidFieldValue(ref path, _) => assert_eq!(path, "payloadBinary[1]"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn launch_options_are_only_sent_when_given() {
+        let kind = MessageKind::Launch {
+            app_id: ApplicationId("CC1AD845".to_owned()),
+            request_id: 1,
+            options: LaunchOptions::new(),
+        };
+
+        assert_eq!(kind.to_payload(), Payload::Utf8(object! {
+            "type" => "LAUNCH",
+            "appId" => "CC1AD845",
+            "requestId" => 1
+        }));
+    }
+
+    #[test]
+    fn launch_options_for_android_tv() {
+        let kind = MessageKind::Launch {
+            app_id: ApplicationId("CC1AD845".to_owned()),
+            request_id: 1,
+            options: LaunchOptions::new()
+                .language("en-US")
+                .supported_app_types(&[AppType::Web, AppType::AndroidTv])
+                .app_params(object! { "contentId" => "abc" })
+                .credentials(Credentials::new("token").credentials_type("android")),
+        };
+
+        assert_eq!(kind.to_payload(), Payload::Utf8(object! {
+            "type" => "LAUNCH",
+            "appId" => "CC1AD845",
+            "requestId" => 1,
+            "language" => "en-US",
+            "supportedAppTypes" => array!["WEB", "ANDROID_TV"],
+            "appParams" => object! { "contentId" => "abc" },
+            "credentialsData" => object! {
+                "credentials" => "token",
+                "credentialsType" => "android"
+            }
+        }));
+    }
+
+    #[test]
+    fn invalid_requests_are_request_errors() {
+        let message = parse_payload("{ \"type\": \"INVALID_REQUEST\", \"requestId\": 7, \
+                                       \"reason\": \"DUPLICATE_REQUEST_ID\" }").unwrap();
+
+        assert_eq!(message.kind.request_error(),
+                   Some((7, RequestError::InvalidRequest(InvalidRequestReason::DuplicateRequestId))));
+    }
+
+    #[test]
+    fn unknown_launch_error_reasons_are_kept() {
+        let message = parse_payload("{ \"type\": \"LAUNCH_ERROR\", \"requestId\": 3, \
+                                       \"reason\": \"SOMETHING_NEW\" }").unwrap();
+
+        assert_eq!(message.kind, MessageKind::LaunchError {
+            reason: LaunchErrorReason::Other("SOMETHING_NEW".to_owned()),
+            request_id: 3,
+        });
+    }
+
+    #[test]
+    fn multizone_messages_depend_on_the_namespace() {
+        let payload = "{ \"type\": \"SET_VOLUME\", \"deviceId\": \"kitchen\", \"requestId\": 2, \
+                         \"volume\": { \"level\": 0.5 } }";
+
+        let message = parse_payload_on(::namespace::multizone(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::MultizoneSetVolume {
+            device_id: "kitchen".to_owned(),
+            level: Some(VolumeLevel(0.5)),
+            muted: None,
+            request_id: 2,
+        });
+
+        let message = parse_payload_on(::namespace::receiver(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::SetVolume { level: Some(VolumeLevel(0.5)), muted: None, request_id: 2 });
+    }
+
+    #[test]
+    fn parse_multizone_status() {
+        let payload = "{ \"type\": \"MULTIZONE_STATUS\", \"requestId\": 5, \"status\": { \
+                         \"devices\": [ { \"deviceId\": \"kitchen\", \"name\": \"Kitchen speaker\", \
+                                          \"capabilities\": 4, \"volume\": { \"level\": 0.25, \"muted\": false } } ], \
+                         \"isMultichannel\": false } }";
+
+        let message = parse_payload_on(::namespace::multizone(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::MultizoneStatus {
+            status: GroupStatus {
+                members: vec![Member {
+                    device_id: "kitchen".to_owned(),
+                    name: "Kitchen speaker".to_owned(),
+                    capabilities: Some(4),
+                    volume: MemberVolume { level: VolumeLevel(0.25), muted: false },
+                }],
+                is_multichannel: Some(false),
+            },
+            request_id: 5,
+        });
+    }
+
+    #[test]
+    fn media_volume_is_separate_from_device_volume() {
+        let payload = "{ \"type\": \"SET_VOLUME\", \"mediaSessionId\": 1, \"requestId\": 6, \
+                         \"volume\": { \"muted\": true } }";
+
+        let message = parse_payload_on(::namespace::media(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::MediaSetVolume {
+            media_session_id: 1,
+            level: None,
+            muted: Some(true),
+            request_id: 6,
+        });
+    }
+
+    #[test]
+    fn parse_media_status() {
+        let payload = "{ \"type\": \"MEDIA_STATUS\", \"requestId\": 0, \"status\": [ { \
+                         \"mediaSessionId\": 1, \"playerState\": \"PLAYING\", \"currentTime\": 12.5, \
+                         \"playbackRate\": 1, \"supportedMediaCommands\": 15, \
+                         \"volume\": { \"level\": 0.5, \"muted\": false } } ] }";
+
+        let message = parse_payload_on(::namespace::media(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::MediaStatus {
+            statuses: vec![MediaStatus {
+                media_session_id: 1,
+                player_state: PlayerState::Playing,
+                idle_reason: None,
+                current_time: 12.5,
+                playback_rate: 1.0,
+                volume: MediaVolume { level: Some(VolumeLevel(0.5)), muted: Some(false) },
+                supported_media_commands: 15,
+                media: None,
+                live_seekable_range: None,
+            }],
+            request_id: 0,
+        });
+    }
+
+    #[test]
+    fn parse_live_seekable_range() {
+        let payload = "{ \"type\": \"MEDIA_STATUS\", \"status\": [ { \
+                         \"mediaSessionId\": 1, \"playerState\": \"PLAYING\", \
+                         \"liveSeekableRange\": { \"start\": 0, \"end\": 3600.5, \"isLiveDone\": false } } ] }";
+
+        let message = parse_payload_on(::namespace::media(), payload).unwrap();
+        match message.kind {
+            MessageKind::MediaStatus { ref statuses, request_id } => {
+                assert_eq!(request_id, 0);
+                assert_eq!(statuses[0].live_seekable_range, Some(LiveSeekableRange {
+                    start: Some(0.0),
+                    end: Some(3600.5),
+                    is_moving_window: None,
+                    is_live_done: Some(false),
+                }));
+            },
+            ref kind => panic!("expected a media status, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn relative_seeks_use_relative_time() {
+        let payload = "{ \"type\": \"SEEK\", \"mediaSessionId\": 1, \"requestId\": 9, \
+                         \"relativeTime\": -30, \"resumeState\": \"PLAYBACK_START\" }";
+
+        let message = parse_payload_on(::namespace::media(), payload).unwrap();
+        assert_eq!(message.kind, MessageKind::MediaSeek {
+            media_session_id: 1,
+            position: SeekPosition::Relative(-30.0),
+            resume_state: Some(ResumeState::PlaybackStart),
+            request_id: 9,
+        });
+    }
+
+    #[test]
+    fn unknown_resume_states_are_invalid() {
+        let error = parse_payload_on(::namespace::media(),
+                                     "{ \"type\": \"SEEK\", \"mediaSessionId\": 1, \"requestId\": 9, \
+                                        \"currentTime\": 10, \"resumeState\": \"PLAYBACK_REWIND\" }").unwrap_err();
+
+        match *error.kind() {
+            ErrorKind::InvalidFieldValue(ref path, ref value) => {
+                assert_eq!(path, "resumeState");
+                assert_eq!(value, "PLAYBACK_REWIND");
+            },
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn stop_depends_on_the_namespace() {
+        let message = parse_payload_on(::namespace::media(),
+                                       "{ \"type\": \"STOP\", \"mediaSessionId\": 3, \"requestId\": 4 }").unwrap();
+        assert_eq!(message.kind, MessageKind::MediaStop { media_session_id: 3, request_id: 4 });
+    }
+
+    #[test]
+    fn load_requires_media_information() {
+        let error = parse_payload_on(::namespace::media(),
+                                     "{ \"type\": \"LOAD\", \"media\": \"song.mp3\", \"requestId\": 4 }").unwrap_err();
+
+        match *error.kind() {
+            ErrorKind::InvalidFieldType(ref path, _) => assert_eq!(path, "media"),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn media_error_responses_are_request_errors() {
+        let message = parse_payload_on(::namespace::media(), "{ \"type\": \"LOAD_FAILED\", \"requestId\": 8 }").unwrap();
+        assert_eq!(message.kind.request_error(), Some((8, RequestError::LoadFailed)));
+    }
+
+    #[test]
+    fn app_availability_responses_use_response_type() {
+        let message = parse_payload("{ \"responseType\": \"GET_APP_AVAILABILITY\", \"requestId\": 4, \
+                                       \"availability\": { \"CC1AD845\": \"APP_AVAILABLE\", \
+                                                           \"Netflix\": \"APP_UNAVAILABLE\" } }").unwrap();
+
+        let mut availability = HashMap::new();
+        availability.insert(ApplicationId("CC1AD845".to_owned()), AppAvailability::Available);
+        availability.insert(ApplicationId("Netflix".to_owned()), AppAvailability::Unavailable);
+
+        assert_eq!(message.kind, MessageKind::AppAvailability {
+            availability: availability,
+            request_id: 4,
+        });
+    }
+
+    quickcheck! {
+        fn messages_survive_a_round_trip(message: Message) -> bool {
+            let bytes = message.as_raw_bytes().unwrap();
+            Message::from_raw_bytes(&bytes).unwrap() == message
+        }
+
+        fn messages_survive_a_json_round_trip(message: Message) -> bool {
+            Message::from_json(&message.to_json()).unwrap() == message
+        }
+    }
 }
@@ -2,13 +2,25 @@
 
 pub extern crate gcast_wire as wire;
 
-pub use self::message::{Message, MessageKind, Namespace, EndpointName};
+pub use self::message::{Message, MessageKind, Payload, Namespace, EndpointName};
 pub use self::status::{Status, Volume};
 pub use self::errors::{Error, ErrorKind};
+pub use self::launch::{LaunchOptions, AppType, Credentials};
+pub use self::request_error::{RequestError, InvalidRequestReason, LaunchErrorReason};
+pub use self::device_info::DeviceDescription;
+pub use self::multizone::{GroupStatus, Member, MemberVolume};
+pub use self::media::{MediaStatus, MediaSessionId, MediaVolume, PlayerState,
+                      LiveSeekableRange, SeekPosition, ResumeState};
 
 pub mod message;
 pub mod status;
 pub mod errors;
+pub mod field;
+pub mod launch;
+pub mod request_error;
+pub mod device_info;
+pub mod multizone;
+pub mod media;
 
 extern crate protobuf;
 extern crate uuid;
@@ -17,10 +29,27 @@ extern crate error_chain;
 #[macro_use]
 extern crate json;
 
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
 /// An identifier for an application.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ApplicationId(pub String);
 
+/// Whether a receiver is able to run an application.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AppAvailability
+{
+    /// The application can be launched.
+    Available,
+    /// The application is not supported by the receiver.
+    Unavailable,
+}
+
+/// An identifier used to match responses to the requests that caused them.
+pub type RequestId = i64;
+
 /// A session ID of a running application.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SessionId(pub uuid::Uuid);
@@ -33,25 +62,60 @@ pub struct VolumeLevel(pub f32);
 pub mod namespace {
     use super::Namespace;
 
+    const CONNECTION: &'static str = "urn:x-cast:com.google.cast.tp.connection";
+    const HEARTBEAT: &'static str = "urn:x-cast:com.google.cast.tp.heartbeat";
+    const RECEIVER: &'static str = "urn:x-cast:com.google.cast.receiver";
+    const DISCOVERY: &'static str = "urn:x-cast:com.google.cast.receiver.discovery";
+    const MEDIA: &'static str = "urn:x-cast:com.google.cast.media";
+    const MULTIZONE: &'static str = "urn:x-cast:com.google.cast.multizone";
+    const DEVICE_AUTH: &'static str = "cast:com.google.cast.tp.deviceauth";
+
     /// Gets the 'connection' namespace.
     pub fn connection() -> Namespace {
-        Namespace("urn:x-cast:com.google.cast.tp.connection".to_owned())
+        Namespace(CONNECTION.to_owned())
     }
 
     /// Gets the 'heartbeat' namespace.
     ///
     /// This is used for sending `PING` and `PONG`.
     pub fn heartbeat() -> Namespace {
-        Namespace("urn:x-cast:com.google.cast.tp.heartbeat".to_owned())
+        Namespace(HEARTBEAT.to_owned())
     }
 
     /// Gets the 'receiver' namespace.
     pub fn receiver() -> Namespace {
-        Namespace("urn:x-cast:com.google.cast.receiver".to_owned())
+        Namespace(RECEIVER.to_owned())
+    }
+
+    /// Gets the 'receiver discovery' namespace.
+    ///
+    /// This is used to ask a receiver to describe itself.
+    pub fn discovery() -> Namespace {
+        Namespace(DISCOVERY.to_owned())
+    }
+
+    /// Gets the 'media' namespace.
+    ///
+    /// This is used to control media playback in applications
+    /// such as the Default Media Receiver.
+    pub fn media() -> Namespace {
+        Namespace(MEDIA.to_owned())
+    }
+
+    /// Gets the 'multizone' namespace.
+    ///
+    /// This is used to manage the speakers in a Cast audio group.
+    pub fn multizone() -> Namespace {
+        Namespace(MULTIZONE.to_owned())
     }
 
     /// Gets the 'deviceauth' namespace.
     pub fn device_auth() -> Namespace {
-        Namespace("cast:com.google.cast.tp.deviceauth".to_owned())
+        Namespace(DEVICE_AUTH.to_owned())
+    }
+
+    /// Gets the names of all of the namespaces that are interpreted by this crate.
+    pub fn builtin() -> &'static [&'static str] {
+        &[CONNECTION, HEARTBEAT, RECEIVER, DISCOVERY, MEDIA, MULTIZONE, DEVICE_AUTH]
     }
 }
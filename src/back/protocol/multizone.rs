@@ -0,0 +1,99 @@
+use {VolumeLevel, Error};
+use field::Field;
+
+use json;
+
+/// The status of a Cast audio group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupStatus
+{
+    /// The speakers that are members of the group.
+    pub members: Vec<Member>,
+    /// Whether the group plays different channels on different speakers,
+    /// such as a stereo pair.
+    pub is_multichannel: Option<bool>,
+}
+
+/// A speaker that is a member of a Cast audio group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member
+{
+    /// The unique identifier of the speaker.
+    pub device_id: String,
+    /// The name the user has given the speaker.
+    pub name: String,
+    /// The features of the speaker, as `device_info::capability` flags.
+    pub capabilities: Option<u64>,
+    /// The volume of this speaker alone.
+    pub volume: MemberVolume,
+}
+
+/// The volume of a single speaker within a group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemberVolume
+{
+    /// The current volume level.
+    pub level: VolumeLevel,
+    /// Whether or not the speaker is muted.
+    pub muted: bool,
+}
+
+impl GroupStatus
+{
+    /// Reads the status from the payload of a `MULTIZONE_STATUS` message.
+    pub fn from_field(status: &Field) -> Result<Self, Error> {
+        let members: Result<Vec<_>, _> = status.get("devices").as_array()?.iter().map(Member::from_field).collect();
+
+        Ok(GroupStatus {
+            members: members?,
+            is_multichannel: status.get("isMultichannel").optional(Field::as_bool)?,
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let members: Vec<_> = self.members.iter().map(Member::to_json).collect();
+
+        let mut status = object! {
+            "devices" => members
+        };
+
+        if let Some(is_multichannel) = self.is_multichannel { status["isMultichannel"] = is_multichannel.into() };
+        status
+    }
+
+    /// Gets a member of the group by its device ID.
+    pub fn member(&self, device_id: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.device_id == device_id)
+    }
+}
+
+impl Member
+{
+    pub fn from_field(member: &Field) -> Result<Self, Error> {
+        let volume = member.get("volume");
+
+        Ok(Member {
+            device_id: member.get("deviceId").as_str()?.to_owned(),
+            name: member.get("name").as_str()?.to_owned(),
+            capabilities: member.get("capabilities").optional(Field::as_i64)?.map(|capabilities| capabilities as u64),
+            volume: MemberVolume {
+                level: VolumeLevel(volume.get("level").as_f32()?),
+                muted: volume.get("muted").as_bool()?,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut member = object! {
+            "deviceId" => &self.device_id[..],
+            "name" => &self.name[..],
+            "volume" => object! {
+                "level" => self.volume.level.0,
+                "muted" => self.volume.muted
+            }
+        };
+
+        if let Some(capabilities) = self.capabilities { member["capabilities"] = (capabilities as i64).into() };
+        member
+    }
+}
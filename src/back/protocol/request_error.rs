@@ -0,0 +1,117 @@
+use std::fmt;
+
+/// An error sent by the receiver in response to a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestError
+{
+    /// The request was not understood (`INVALID_REQUEST`).
+    InvalidRequest(InvalidRequestReason),
+    /// An application could not be launched (`LAUNCH_ERROR`).
+    LaunchFailed(LaunchErrorReason),
+    /// Media could not be loaded (`LOAD_FAILED`).
+    LoadFailed,
+    /// Loading media was cancelled (`LOAD_CANCELLED`).
+    LoadCancelled,
+}
+
+/// The reason a request was rejected with `INVALID_REQUEST`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidRequestReason
+{
+    /// The command is not known to the receiver.
+    InvalidCommand,
+    /// A request with the same ID is already in progress.
+    DuplicateRequestId,
+    /// The media session does not exist.
+    InvalidMediaSessionId,
+    /// The parameters of the request are invalid.
+    InvalidParams,
+    /// A reason that this crate does not know about.
+    Other(String),
+}
+
+/// The reason an application could not be launched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LaunchErrorReason
+{
+    /// The application does not exist.
+    NotFound,
+    /// The launch was cancelled, usually by a newer request.
+    Cancelled,
+    /// The sender is not allowed to launch the application.
+    NotAllowed,
+    /// The parameters of the launch request are invalid.
+    BadParameter,
+    /// The application did not start in time.
+    CastInitTimeout,
+    /// A reason that this crate does not know about.
+    Other(String),
+}
+
+impl InvalidRequestReason
+{
+    /// Gets the name used for the reason in messages.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            InvalidRequestReason::InvalidCommand => "INVALID_COMMAND",
+            InvalidRequestReason::DuplicateRequestId => "DUPLICATE_REQUEST_ID",
+            InvalidRequestReason::InvalidMediaSessionId => "INVALID_MEDIA_SESSION_ID",
+            InvalidRequestReason::InvalidParams => "INVALID_PARAMS",
+            InvalidRequestReason::Other(ref reason) => &reason[..],
+        }
+    }
+}
+
+impl<'a> From<&'a str> for InvalidRequestReason
+{
+    fn from(reason: &'a str) -> Self {
+        match reason {
+            "INVALID_COMMAND" => InvalidRequestReason::InvalidCommand,
+            "DUPLICATE_REQUEST_ID" => InvalidRequestReason::DuplicateRequestId,
+            "INVALID_MEDIA_SESSION_ID" => InvalidRequestReason::InvalidMediaSessionId,
+            "INVALID_PARAMS" => InvalidRequestReason::InvalidParams,
+            other => InvalidRequestReason::Other(other.to_owned()),
+        }
+    }
+}
+
+impl LaunchErrorReason
+{
+    /// Gets the name used for the reason in messages.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            LaunchErrorReason::NotFound => "NOT_FOUND",
+            LaunchErrorReason::Cancelled => "CANCELLED",
+            LaunchErrorReason::NotAllowed => "NOT_ALLOWED",
+            LaunchErrorReason::BadParameter => "BAD_PARAMETER",
+            LaunchErrorReason::CastInitTimeout => "CAST_INIT_TIMEOUT",
+            LaunchErrorReason::Other(ref reason) => &reason[..],
+        }
+    }
+}
+
+impl<'a> From<&'a str> for LaunchErrorReason
+{
+    fn from(reason: &'a str) -> Self {
+        match reason {
+            "NOT_FOUND" => LaunchErrorReason::NotFound,
+            "CANCELLED" => LaunchErrorReason::Cancelled,
+            "NOT_ALLOWED" => LaunchErrorReason::NotAllowed,
+            "BAD_PARAMETER" => LaunchErrorReason::BadParameter,
+            "CAST_INIT_TIMEOUT" => LaunchErrorReason::CastInitTimeout,
+            other => LaunchErrorReason::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for RequestError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RequestError::InvalidRequest(ref reason) => write!(fmt, "invalid request: {}", reason.as_str()),
+            RequestError::LaunchFailed(ref reason) => write!(fmt, "failed to launch application: {}", reason.as_str()),
+            RequestError::LoadFailed => write!(fmt, "failed to load media"),
+            RequestError::LoadCancelled => write!(fmt, "loading media was cancelled"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use {ApplicationId, VolumeLevel, Error};
+use {ApplicationId, VolumeLevel, Namespace, EndpointName, Error};
+use field::Field;
 
 use std::fmt;
 
@@ -13,6 +14,16 @@ pub struct Status
     pub volume: Volume,
     /// The currently running applications.
     pub applications: Vec<Application>,
+    /// Whether the Cast device is the active input on the TV.
+    ///
+    /// This is only known for devices that support HDMI-CEC.
+    pub is_active_input: Option<bool>,
+    /// Whether the TV the Cast device is connected to is in standby.
+    ///
+    /// This is only known for devices that support HDMI-CEC.
+    pub is_stand_by: Option<bool>,
+    /// The user equalizer settings, if the device has any.
+    pub user_eq: Option<json::JsonValue>,
 }
 
 /// The volume status of a Cast receiver.
@@ -23,6 +34,8 @@ pub struct Volume
     ///
     /// Possible values:
     /// * `"attenuation"`
+    /// * `"master"`
+    /// * `"fixed"` - the volume cannot be changed by senders.
     pub control_type: String,
     /// The current volume level.
     pub level: VolumeLevel,
@@ -45,59 +58,164 @@ pub struct Application
     pub session_id: Uuid,
     /// The status text.
     pub status_text: String,
+    /// The application type.
+    ///
+    /// Possible values:
+    /// * `"WEB"`
+    /// * `"ANDROID_TV"`
+    pub app_type: Option<String>,
+    /// The URL of the application icon.
+    pub icon_url: Option<String>,
+    /// An identifier shared between the variants of an application.
+    pub universal_app_id: Option<String>,
+    /// Whether the application was launched from the cloud rather than
+    /// from a sender on the local network.
+    pub launched_from_cloud: Option<bool>,
+    /// The endpoint used to send messages to the application.
+    pub transport_id: Option<EndpointName>,
+    /// The namespaces the application communicates over.
+    pub namespaces: Vec<Namespace>,
 }
 
 impl Status
 {
     /// Reads the status from the payload of a `RECEIVER_STATUS` message.
     pub fn from_json(status: &json::JsonValue) -> Result<Self, Error> {
-        let application_data = &status["applications"];
-
-        let volume = Volume::from_json(&status["volume"])?;
+        Status::from_field(&Field::root(status, "status"))
+    }
 
-        let applications = if application_data.is_array() {
-            let result: Result<Vec<_>, _> = application_data.members().map(|app_data| {
-                Application::from_json(app_data)
-            }).collect();
+    /// Reads the status from a field inside a message payload.
+    pub fn from_field(status: &Field) -> Result<Self, Error> {
+        let volume = Volume::from_field(&status.get("volume"))?;
 
-            result?
-        } else {
-            Vec::new()
+        let applications = match status.get("applications").optional(Field::as_array)? {
+            Some(application_fields) => {
+                let result: Result<Vec<_>, _> = application_fields.iter().map(Application::from_field).collect();
+                result?
+            },
+            None => Vec::new(),
         };
 
         Ok(Status {
             volume: volume,
             applications: applications,
+            is_active_input: status.get("isActiveInput").optional(Field::as_bool)?,
+            is_stand_by: status.get("isStandBy").optional(Field::as_bool)?,
+            user_eq: status.get("userEq").optional(|field| Ok(field.value.clone()))?,
         })
     }
+
+    /// Builds the payload of a `RECEIVER_STATUS` message.
+    pub fn to_json(&self) -> json::JsonValue {
+        let applications: Vec<_> = self.applications.iter().map(Application::to_json).collect();
+
+        let mut status = object! {
+            "volume" => self.volume.to_json(),
+            "applications" => applications
+        };
+
+        if let Some(is_active_input) = self.is_active_input { status["isActiveInput"] = is_active_input.into() };
+        if let Some(is_stand_by) = self.is_stand_by { status["isStandBy"] = is_stand_by.into() };
+        if let Some(ref user_eq) = self.user_eq { status["userEq"] = user_eq.clone() };
+
+        status
+    }
 }
 
 impl Volume
 {
     pub fn from_json(volume: &json::JsonValue) -> Result<Self, Error> {
+        Volume::from_field(&Field::root(volume, "volume"))
+    }
+
+    pub fn from_field(volume: &Field) -> Result<Self, Error> {
         Ok(Volume {
-            control_type: volume["controlType"].as_str().expect("controlType is missing or not a string").to_owned(),
-            level: VolumeLevel(volume["level"].as_f32().expect("level is missing or not a float")),
-            muted: volume["muted"].as_bool().expect("muted is missing or not a bool"),
-            step_interval: VolumeLevel(volume["stepInterval"].as_f32().expect("stepInterval is missing or not a float")),
+            control_type: volume.get("controlType").as_str()?.to_owned(),
+            level: VolumeLevel(volume.get("level").as_f32()?),
+            muted: volume.get("muted").as_bool()?,
+            step_interval: VolumeLevel(volume.get("stepInterval").as_f32()?),
         })
     }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        object! {
+            "controlType" => &self.control_type[..],
+            "level" => self.level.0,
+            "muted" => self.muted,
+            "stepInterval" => self.step_interval.0
+        }
+    }
+
+    /// Checks whether the volume is fixed and cannot be changed by senders.
+    pub fn is_fixed(&self) -> bool {
+        self.control_type == "fixed"
+    }
+
+    /// Gets the level one step above the current level.
+    pub fn level_up(&self) -> VolumeLevel {
+        VolumeLevel(self.level.0 + self.step_interval.0).clamp()
+    }
+
+    /// Gets the level one step below the current level.
+    pub fn level_down(&self) -> VolumeLevel {
+        VolumeLevel(self.level.0 - self.step_interval.0).clamp()
+    }
 }
 
 impl Application
 {
     pub fn from_json(application: &json::JsonValue) -> Result<Self, Error> {
-        let session_id_text = application["sessionId"].as_str().expect("sessionId is missing or not a string");
-        let session_id = Uuid::parse_str(session_id_text)?;
+        Application::from_field(&Field::root(application, "application"))
+    }
+
+    pub fn from_field(application: &Field) -> Result<Self, Error> {
+        let namespaces = match application.get("namespaces").optional(Field::as_array)? {
+            Some(namespace_fields) => {
+                let result: Result<Vec<_>, Error> = namespace_fields.iter().map(|namespace| {
+                    Ok(Namespace(namespace.get("name").as_str()?.to_owned()))
+                }).collect();
+                result?
+            },
+            None => Vec::new(),
+        };
 
         Ok(Application {
-            id: ApplicationId(application["appId"].as_str().expect("appId is missing or not a string").to_owned()),
-            display_name: application["displayName"].as_str().expect("displayName is missing or not a string").to_owned(),
-            is_idle_screen: application["isIdleScreen"].as_bool().expect("isIdleScreen is missing or not a bool").to_owned(),
-            session_id: session_id,
-            status_text: application["statusText"].as_str().expect("statusText is missing or not a string").to_owned(),
+            id: ApplicationId(application.get("appId").as_str()?.to_owned()),
+            display_name: application.get("displayName").as_str()?.to_owned(),
+            is_idle_screen: application.get("isIdleScreen").as_bool()?,
+            session_id: application.get("sessionId").as_uuid()?,
+            status_text: application.get("statusText").as_str()?.to_owned(),
+            app_type: application.get("appType").optional(Field::as_str)?.map(ToOwned::to_owned),
+            icon_url: application.get("iconUrl").optional(Field::as_str)?.map(ToOwned::to_owned),
+            universal_app_id: application.get("universalAppId").optional(Field::as_str)?.map(ToOwned::to_owned),
+            launched_from_cloud: application.get("launchedFromCloud").optional(Field::as_bool)?,
+            transport_id: application.get("transportId").optional(Field::as_str)?.map(|id| EndpointName(id.to_owned())),
+            namespaces: namespaces,
         })
     }
+
+    pub fn to_json(&self) -> json::JsonValue {
+        let namespaces: Vec<_> = self.namespaces.iter().map(|namespace| {
+            object! { "name" => &namespace.0[..] }
+        }).collect();
+
+        let mut application = object! {
+            "appId" => &self.id.0[..],
+            "displayName" => &self.display_name[..],
+            "isIdleScreen" => self.is_idle_screen,
+            "sessionId" => self.session_id.to_string(),
+            "statusText" => &self.status_text[..],
+            "namespaces" => namespaces
+        };
+
+        if let Some(ref app_type) = self.app_type { application["appType"] = app_type.clone().into() };
+        if let Some(ref icon_url) = self.icon_url { application["iconUrl"] = icon_url.clone().into() };
+        if let Some(ref universal_app_id) = self.universal_app_id { application["universalAppId"] = universal_app_id.clone().into() };
+        if let Some(launched_from_cloud) = self.launched_from_cloud { application["launchedFromCloud"] = launched_from_cloud.into() };
+        if let Some(ref transport_id) = self.transport_id { application["transportId"] = transport_id.0.clone().into() };
+
+        application
+    }
 }
 
 impl VolumeLevel
@@ -105,6 +223,11 @@ impl VolumeLevel
     pub fn max() -> Self { VolumeLevel(1.0) }
     pub fn min() -> Self { VolumeLevel(0.0) }
 
+    /// Limits the level to the range between `min` and `max`.
+    pub fn clamp(self) -> Self {
+        VolumeLevel(self.0.max(Self::min().0).min(Self::max().0))
+    }
+
     /// Gets the volume percentage.
     pub fn percentage(&self) -> f32 {
         self.0 * 100.0
@@ -121,7 +244,7 @@ impl fmt::Debug for VolumeLevel
 mod test
 {
     use super::*;
-    use ApplicationId;
+    use {ApplicationId, Namespace, EndpointName, ErrorKind};
     use json;
     use uuid::Uuid;
 
@@ -166,6 +289,9 @@ mod test
                 muted: true,
                 step_interval: VolumeLevel(0.125),
             },
+            is_active_input: None,
+            is_stand_by: None,
+            user_eq: None,
         });
     }
 
@@ -181,9 +307,49 @@ mod test
                 muted: false,
                 step_interval: VolumeLevel(0.1),
             },
+            is_active_input: None,
+            is_stand_by: None,
+            user_eq: None,
         });
     }
 
+    #[test]
+    fn missing_application_field_names_its_path() {
+        let application = object! {
+            "appId" => "YouTube",
+            "displayName" => "YouTube",
+            "isIdleScreen" => false,
+            "statusText" => "YouTube TV"
+        };
+
+        let json = object! { "volume" => example_volume(),
+                             "applications" => vec![application] };
+
+        match Status::from_json(&json).unwrap_err().kind() {
+            &ErrorKind::MissingField(ref path) => assert_eq!(path, "status.applications[0].sessionId"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn mistyped_volume_field_names_its_path() {
+        let mut volume = example_volume();
+        volume["muted"] = "yes".into();
+
+        match Status::from_json(&object! { "volume" => volume }).unwrap_err().kind() {
+            &ErrorKind::InvalidFieldType(ref path, _) => assert_eq!(path, "status.volume.muted"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let mut volume = example_volume();
+        volume["someNewField"] = 12.into();
+
+        parse_json(object! { "volume" => volume });
+    }
+
     #[test]
     fn parse_youtube_application() {
         let status = parse_json(object! { "volume" => example_volume(),
@@ -195,6 +361,78 @@ mod test
             is_idle_screen: false,
             session_id: Uuid::parse_str("e32a8e92-29cd-4afb-9d2b-6314040022d8").unwrap(),
             status_text: "YouTube TV".to_owned(),
+            app_type: None,
+            icon_url: None,
+            universal_app_id: None,
+            launched_from_cloud: None,
+            transport_id: None,
+            namespaces: Vec::new(),
         }]);
     }
+
+    #[test]
+    fn parse_standby_and_active_input() {
+        let status = parse_json(object! { "volume" => example_volume(),
+                                          "isActiveInput" => false,
+                                          "isStandBy" => true });
+
+        assert_eq!(status.is_active_input, Some(false));
+        assert_eq!(status.is_stand_by, Some(true));
+    }
+
+    #[test]
+    fn parse_application_metadata() {
+        let mut application = youtube_application();
+        application["appType"] = "WEB".into();
+        application["iconUrl"] = "https://example.com/youtube.png".into();
+        application["universalAppId"] = "233637DE".into();
+        application["launchedFromCloud"] = false.into();
+        application["transportId"] = "e32a8e92-29cd-4afb-9d2b-6314040022d8".into();
+        application["namespaces"] = vec![
+            object! { "name" => "urn:x-cast:com.google.cast.media" },
+            object! { "name" => "urn:x-cast:com.google.youtube.mdx" },
+        ].into();
+
+        let status = parse_json(object! { "volume" => example_volume(),
+                                          "applications" => vec![application] });
+        let application = &status.applications[0];
+
+        assert_eq!(application.app_type, Some("WEB".to_owned()));
+        assert_eq!(application.icon_url, Some("https://example.com/youtube.png".to_owned()));
+        assert_eq!(application.universal_app_id, Some("233637DE".to_owned()));
+        assert_eq!(application.launched_from_cloud, Some(false));
+        assert_eq!(application.transport_id, Some(EndpointName("e32a8e92-29cd-4afb-9d2b-6314040022d8".to_owned())));
+        assert_eq!(application.namespaces, &[
+            Namespace("urn:x-cast:com.google.cast.media".to_owned()),
+            Namespace("urn:x-cast:com.google.youtube.mdx".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn volume_steps_are_clamped() {
+        let mut volume = Volume {
+            control_type: "attenuation".to_owned(),
+            level: VolumeLevel(0.5),
+            muted: false,
+            step_interval: VolumeLevel(0.25),
+        };
+
+        assert_eq!(volume.level_up(), VolumeLevel(0.75));
+        assert_eq!(volume.level_down(), VolumeLevel(0.25));
+
+        volume.level = VolumeLevel(0.9);
+        assert_eq!(volume.level_up(), VolumeLevel::max());
+
+        volume.level = VolumeLevel(0.1);
+        assert_eq!(volume.level_down(), VolumeLevel::min());
+    }
+
+    #[test]
+    fn fixed_volume() {
+        let mut volume = Volume::from_json(&example_volume()).unwrap();
+        assert!(!volume.is_fixed());
+
+        volume.control_type = "fixed".to_owned();
+        assert!(volume.is_fixed());
+    }
 }
@@ -0,0 +1,122 @@
+//! Forwards a sender's connection to a Cast device, logging every message.
+//!
+//! Usage:
+//!
+//! gcast-proxy [--listen ADDRESS:PORT] [--namespace NAMESPACE]...
+//!             [--exclude NAMESPACE]... [--replace FROM=TO]... DEVICE[:PORT]
+//!
+//! Messages are written to stdout as JSON lines. Point a sender at the
+//! listening address, for example by connecting to it by IP address.
+
+extern crate gcast;
+
+use gcast::{Namespace, Payload};
+use gcast::back::protocol::{Message, MessageKind};
+use gcast::recording::Direction;
+use gcast::proxy::{Options, Proxy};
+
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::{env, io, process, thread};
+
+const USAGE: &'static str = "usage: gcast-proxy [--listen ADDRESS:PORT] [--namespace NAMESPACE]... \
+                             [--exclude NAMESPACE]... [--replace FROM=TO]... DEVICE[:PORT]";
+
+/// A replacement of text in JSON payloads.
+#[derive(Clone, Debug)]
+struct Replacement
+{
+    from: String,
+    to: String,
+}
+
+fn main() {
+    let mut listen = "0.0.0.0:8009".to_owned();
+    let mut target = None;
+    let mut namespaces = Vec::new();
+    let mut excluded_namespaces = Vec::new();
+    let mut replacements = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--listen" => listen = value(&mut args),
+            "--namespace" => namespaces.push(Namespace(value(&mut args))),
+            "--exclude" => excluded_namespaces.push(Namespace(value(&mut args))),
+            "--replace" => {
+                let replacement = value(&mut args);
+                let separator = replacement.find('=').unwrap_or_else(|| usage());
+
+                replacements.push(Replacement {
+                    from: replacement[..separator].to_owned(),
+                    to: replacement[separator + 1..].to_owned(),
+                });
+            },
+            "--help" | "-h" => usage(),
+            _ if target.is_none() && !arg.starts_with("--") => target = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let target = target.unwrap_or_else(|| usage());
+    let target = resolve(&target).unwrap_or_else(|| fail(&format!("could not resolve '{}'", target)));
+
+    let mut options = Options::new(target);
+    options.namespaces = namespaces;
+    options.excluded_namespaces = excluded_namespaces;
+
+    if !replacements.is_empty() {
+        options = options.rewrite(move |_: Direction, message: Message| Some(replace(&replacements, message)));
+    }
+
+    let listener = TcpListener::bind(&listen[..]).unwrap_or_else(|e| fail(&e.to_string()));
+    let proxy = Proxy::start(listener, options, io::stdout()).unwrap_or_else(|e| fail(&e.to_string()));
+    eprintln!("forwarding {} to {}", proxy.address(), target);
+
+    loop {
+        thread::park();
+    }
+}
+
+/// Takes the value of an option.
+fn value<I>(args: &mut I) -> String where I: Iterator<Item=String> {
+    args.next().unwrap_or_else(|| usage())
+}
+
+/// Resolves a device address, using the Cast port if none is given.
+fn resolve(address: &str) -> Option<SocketAddr> {
+    let address = if address.contains(':') {
+        address.to_owned()
+    } else {
+        format!("{}:{}", address, gcast::discovery::DEFAULT_PORT)
+    };
+
+    address.to_socket_addrs().ok().and_then(|mut addresses| addresses.next())
+}
+
+/// Replaces text in the payload of a message.
+///
+/// Messages that no longer make sense after the replacement are left alone.
+fn replace(replacements: &[Replacement], message: Message) -> Message {
+    let text = match message.payload() {
+        Payload::Utf8(data) => gcast::json::stringify(data),
+        Payload::Binary(..) => return message,
+    };
+
+    let replaced = replacements.iter().fold(text, |text, replacement| text.replace(&replacement.from, &replacement.to));
+    let kind = gcast::json::parse(&replaced).ok().and_then(|data| MessageKind::from_json(&message.namespace, &data).ok());
+
+    match kind {
+        Some(kind) => Message { kind: kind, ..message },
+        None => message,
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(reason: &str) -> ! {
+    eprintln!("error: {}", reason);
+    process::exit(1);
+}
@@ -0,0 +1,66 @@
+//! Plays a recording of a device back without a network, printing the
+//! events that it raises.
+//!
+//! Usage:
+//!
+//! gcast-replay RECORDING
+//!
+//! Recordings are made with `Device::record`. Each received packet is
+//! printed with the line it was recorded on, followed by its events or
+//! the error it caused.
+
+extern crate gcast;
+extern crate uuid;
+
+use gcast::DeviceInfo;
+use gcast::recording::{Recording, Replayer};
+
+use std::net::Ipv4Addr;
+use std::{env, process};
+
+use uuid::Uuid;
+
+const USAGE: &'static str = "usage: gcast-replay RECORDING";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = match (args.next(), args.next()) {
+        (Some(ref path), None) if !path.starts_with("-") => path.clone(),
+        _ => usage(),
+    };
+
+    let recording = Recording::open(&path).unwrap_or_else(|e| fail(&e.to_string()));
+
+    // The device is never connected to, so it can be anywhere.
+    let info = DeviceInfo {
+        ip_addr: Ipv4Addr::new(0, 0, 0, 0),
+        port: gcast::discovery::DEFAULT_PORT,
+        uuid: Uuid::nil(),
+        name: None,
+    };
+    let mut replayer = Replayer::new(info, recording);
+
+    while let Some(result) = replayer.step() {
+        let entry = replayer.current().expect("a packet was just played");
+
+        match result {
+            Ok(events) => {
+                println!("line {} ({:.3}s)", entry.line, entry.time);
+                for event in events {
+                    println!("    {:?}", event);
+                }
+            },
+            Err(e) => println!("line {} ({:.3}s): error: {}", entry.line, entry.time, e),
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(reason: &str) -> ! {
+    eprintln!("error: {}", reason);
+    process::exit(1);
+}
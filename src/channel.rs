@@ -0,0 +1,193 @@
+//! Typed messaging over custom namespaces.
+//!
+//! This module is only available when the `channel` feature is enabled.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct PlayerMessage { track: u32 }
+//!
+//! let channel: Channel<PlayerMessage> = Channel::open(&mut device,
+//!     Namespace("urn:x-cast:com.ourteam.player".to_owned()));
+//!
+//! channel.send(&mut device, transport_id, &PlayerMessage { track: 3 })?;
+//!
+//! for event in device.events() {
+//!     if let Some(result) = channel.decode(&event) {
+//!         let (source, message) = result?;
+//!     }
+//! }
+//! ```
+
+use {Device, Event, Error, ErrorKind, Namespace, EndpointName, Payload};
+
+use std::marker::PhantomData;
+
+use json;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+/// A custom namespace that carries messages of a single type.
+///
+/// Messages are converted to and from JSON using serde.
+pub struct Channel<T>
+{
+    namespace: Namespace,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Channel<T>
+    where T: Serialize + DeserializeOwned
+{
+    /// Creates a channel and registers its namespace with a device.
+    pub fn open(device: &mut Device, namespace: Namespace) -> Self {
+        device.register_namespace(namespace.clone());
+
+        Channel {
+            namespace: namespace,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Gets the namespace the channel communicates over.
+    pub fn namespace(&self) -> &Namespace { &self.namespace }
+
+    /// Sends a message to an endpoint, usually the transport ID of an application.
+    pub fn send(&self,
+                device: &mut Device,
+                destination: EndpointName,
+                message: &T) -> Result<(), Error> {
+        let text = serde_json::to_string(message).map_err(|e| {
+            Error::from(ErrorKind::InvalidChannelMessage(e.to_string()))
+        })?;
+        let payload = json::parse(&text).map_err(|e| {
+            Error::from(ErrorKind::InvalidChannelMessage(e.to_string()))
+        })?;
+
+        device.send_json(destination, self.namespace.clone(), payload)
+    }
+
+    /// Decodes an event if it holds a message sent over this channel.
+    ///
+    /// Returns `None` if the event has nothing to do with the channel.
+    pub fn decode(&self, event: &Event) -> Option<Result<(EndpointName, T), Error>> {
+        match *event {
+            Event::NamespaceMessage { ref namespace, ref source, payload: Payload::Utf8(ref payload) }
+                if *namespace == self.namespace => {
+                let text = json::stringify(payload.clone());
+
+                let result = serde_json::from_str(&text).map(|message| {
+                    (source.clone(), message)
+                }).map_err(|e| ErrorKind::InvalidChannelMessage(e.to_string()).into());
+
+                Some(result)
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Channel;
+    use {Device, DeviceInfo, Event, ErrorKind, Namespace, EndpointName, Payload};
+    use back::net::{pipe, Pipe};
+    use back::net::transport::{Reader, write_packet};
+    use back::protocol::{Message, MessageKind};
+
+    use std::collections::BTreeMap;
+    use std::net::Ipv4Addr;
+
+    use json;
+
+    /// A message that is a map from field names to numbers.
+    type Fields = BTreeMap<String, u32>;
+
+    fn device() -> (Device, Pipe) {
+        let (stream, receiver) = pipe();
+        let device = Device::connect_over(DeviceInfo::from_ip_addr(Ipv4Addr::new(127, 0, 0, 1)), stream).unwrap();
+        (device, receiver)
+    }
+
+    fn player() -> Namespace { Namespace("urn:x-cast:com.example.player".to_owned()) }
+
+    fn fields(track: u32) -> Fields {
+        let mut fields = Fields::new();
+        fields.insert("track".to_owned(), track);
+        fields
+    }
+
+    /// Sends a JSON payload from an application to the device, returning the event it raised.
+    fn receive(device: &mut Device, receiver: &mut Pipe, payload: &str) -> Event {
+        let message = Message {
+            source: EndpointName("web-5".to_owned()),
+            destination: EndpointName("sender-0".to_owned()),
+            namespace: player(),
+            kind: MessageKind::Custom { payload: Payload::Utf8(json::parse(payload).unwrap()) },
+        };
+        write_packet(receiver, &message.as_raw_bytes().unwrap()).unwrap();
+        device.update().unwrap();
+
+        let mut events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        events.remove(0)
+    }
+
+    #[test]
+    fn messages_are_sent_as_json() {
+        let (mut device, mut receiver) = device();
+        let channel: Channel<Fields> = Channel::open(&mut device, player());
+
+        channel.send(&mut device, EndpointName("web-5".to_owned()), &fields(3)).unwrap();
+        device.update().unwrap();
+
+        let mut packets = Vec::new();
+        Reader::new().read(&mut receiver, &mut packets).unwrap();
+        let message = Message::from_raw_bytes(packets.last().unwrap()).unwrap();
+
+        assert_eq!(message.namespace, player());
+        assert_eq!(message.kind, MessageKind::Custom { payload: Payload::Utf8(json::parse("{\"track\":3}").unwrap()) });
+    }
+
+    #[test]
+    fn messages_are_decoded_from_events() {
+        let (mut device, mut receiver) = device();
+        let channel: Channel<Fields> = Channel::open(&mut device, player());
+
+        let event = receive(&mut device, &mut receiver, "{\"track\":3}");
+        let (source, message) = channel.decode(&event).unwrap().unwrap();
+
+        assert_eq!(source, EndpointName("web-5".to_owned()));
+        assert_eq!(message, fields(3));
+    }
+
+    #[test]
+    fn undecodable_messages_are_errors() {
+        let (mut device, mut receiver) = device();
+        let channel: Channel<Fields> = Channel::open(&mut device, player());
+
+        let event = receive(&mut device, &mut receiver, "{\"track\":\"three\"}");
+        match channel.decode(&event).unwrap().unwrap_err().kind() {
+            &ErrorKind::InvalidChannelMessage(..) => (),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn other_events_are_left_alone() {
+        let (mut device, _) = device();
+        let channel: Channel<Fields> = Channel::open(&mut device, player());
+        let other: Channel<Fields> = Channel::open(&mut device, Namespace("urn:x-cast:com.example.other".to_owned()));
+
+        let event = Event::NamespaceMessage {
+            namespace: player(),
+            source: EndpointName("web-5".to_owned()),
+            payload: Payload::Utf8(json::parse("{\"track\":3}").unwrap()),
+        };
+
+        assert!(channel.decode(&event).is_some());
+        assert!(other.decode(&event).is_none());
+        assert!(channel.decode(&Event::StatusUpdated).is_none());
+    }
+}
@@ -1,13 +1,24 @@
 //! The core `Device` type.
 
-use {DeviceInfo, ApplicationId, SessionId, Status, Event, Error,
-     VolumeLevel};
+use {DeviceInfo, ApplicationId, SessionId, Status, Event, Error, ErrorKind,
+     Volume, VolumeLevel, Namespace, EndpointName, Payload, LaunchOptions};
+use volume::Fade;
+use media::MediaSession;
+use back::protocol::{RequestId, DeviceDescription, GroupStatus, Member, MediaStatus,
+                     MediaSessionId, SeekPosition, ResumeState};
 use back;
+use back::net::transport::RawPacket;
+use recording::Recorder;
 
-use std::collections::VecDeque;
+use std::collections::{VecDeque, HashSet};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std;
 
+use json;
 use mio;
+use uuid::Uuid;
 
 /// If the internal event queue gets too big, truncate the oldest events.
 const EVENT_QUEUE_MAXIMUM_COUNT: usize = 500;
@@ -15,7 +26,11 @@ const EVENT_QUEUE_MAXIMUM_COUNT: usize = 500;
 /// The string we will use to identify ourselves in messages.
 const SENDER_ID: &'static str = "sender-0";
 /// The string we will use to identify the Cast device in messages.
-const RECEIVER_ID: &'static str = "sender-0";
+///
+/// This is the name of the platform receiver, which handles the receiver,
+/// heartbeat and connection namespaces. Applications have their own
+/// transport IDs, given in `Status::applications`.
+const RECEIVER_ID: &'static str = "receiver-0";
 
 /// A Cast device.
 pub struct Device
@@ -26,10 +41,29 @@ pub struct Device
     /// This will be set and updated upon receiving a
     /// `RECEIVER_STATUS` message.
     status: Option<Status>,
+    /// The description the receiver gave of itself.
+    /// This will be set upon receiving a `DEVICE_INFO` message.
+    description: Option<DeviceDescription>,
+    /// The status of the speaker group, if the device is a Cast audio group.
+    /// This will be set upon receiving a `MULTIZONE_STATUS` message.
+    group: Option<GroupStatus>,
+    /// The media sessions of the running application.
+    /// This will be set and updated upon receiving a `MEDIA_STATUS` message.
+    media_sessions: Vec<MediaSession>,
+    /// The endpoint that the media status was received from.
+    media_endpoint: Option<EndpointName>,
     /// The network connection.
     connection: back::Connection,
     /// A queue that holds the events that have occurred on this device.
     event_queue: VecDeque<Event>,
+    /// The custom namespaces that messages should be raised as events for.
+    namespaces: HashSet<Namespace>,
+    /// The endpoints that we have established a virtual connection to.
+    connected_endpoints: HashSet<EndpointName>,
+    /// The volume fade in progress, if any.
+    fade: Option<Fade>,
+    /// An accumulator used to give us unique request IDs.
+    request_id_accumulator: RequestId,
 }
 
 impl Device
@@ -41,36 +75,148 @@ impl Device
             info: info,
             connection: connection,
             status: None,
+            description: None,
+            group: None,
+            media_sessions: Vec::new(),
+            media_endpoint: None,
             event_queue: VecDeque::new(),
+            namespaces: HashSet::new(),
+            connected_endpoints: HashSet::new(),
+            fade: None,
+            request_id_accumulator: 1,
         }
     }
 
     /// Connect to a receiver.
     pub fn connect(info: DeviceInfo, io: &mut back::net::Io)
         -> Result<Self, Error> {
-        let connection = back::Connection::connect_to(&info, io)?;
-        let mut device = Device::new(info, connection);
+        Device::connect_with_tls(info, io, back::net::tls::default_backend())
+    }
+
+    /// Connects to a Cast device using a particular TLS library.
+    pub fn connect_with_tls(info: DeviceInfo,
+                            io: &mut back::net::Io,
+                            tls: Arc<back::net::tls::TlsBackend>) -> Result<Self, Error> {
+        let transport = back::net::Transport::connect_with_tls(&info, io, tls)?;
+        let mut device = Device::new(info, back::Connection::new(transport));
 
         // Establish a virtual connection
-        device.send_message(back::protocol::namespace::connection(),
-                            back::protocol::MessageKind::Connect)?;
+        device.connect_endpoint(EndpointName(RECEIVER_ID.to_owned()))?;
+
+        Ok(device)
+    }
+
+    /// Connects to a Cast device over a stream that is already open.
+    ///
+    /// This can be used to talk to a device through something other than
+    /// a TCP connection made by mio, such as an SSH tunnel, a TLS stream
+    /// from another library, or an in-memory `back::net::Pipe` in tests.
+    /// The stream must not block, and `Device::update` must be called
+    /// regularly to send and receive messages.
+    pub fn connect_over<S>(info: DeviceInfo, stream: S) -> Result<Self, Error>
+        where S: back::net::transport::Stream + 'static {
+        let connection = back::Connection::new(back::net::Transport::from_stream(stream));
+        let mut device = Device::new(info, connection);
+
+        device.connect_endpoint(EndpointName(RECEIVER_ID.to_owned()))?;
 
         Ok(device)
     }
 
+    /// Creates a device that is not connected to anything.
+    ///
+    /// Packets from a recording can be given to it with `replay_packet`.
+    pub fn replaying(info: DeviceInfo) -> Self {
+        let mut device = Device::new(info, back::Connection::replay());
+        // The recorded device had connected to the receiver, so the
+        // replayed one should behave as if it had too.
+        device.connected_endpoints.insert(EndpointName(RECEIVER_ID.to_owned()));
+        device
+    }
+
     /// Asks the Chromecast for its current status.
-    pub fn update_status(&mut self) -> Result<(), Error> {
+    ///
+    /// Returns the ID of the request.
+    pub fn update_status(&mut self) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
         self.send_message(back::protocol::namespace::receiver(),
-                          back::protocol::MessageKind::GetStatus)
+            back::protocol::MessageKind::GetStatus {
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
     }
 
     /// Launch an application.
-    pub fn launch(&mut self, app_id: ApplicationId) -> Result<(), Error> {
+    ///
+    /// Returns the ID of the request.
+    pub fn launch(&mut self, app_id: ApplicationId) -> Result<RequestId, Error> {
+        self.launch_with_options(app_id, LaunchOptions::new())
+    }
+
+    /// Launch an application with extra options.
+    ///
+    /// Returns the ID of the request.
+    ///
+    /// ```ignore
+    /// let options = LaunchOptions::new()
+    ///     .language("en-US")
+    ///     .supported_app_types(&[AppType::Web, AppType::AndroidTv]);
+    ///
+    /// device.launch_with_options(app_id, options)?;
+    /// ```
+    pub fn launch_with_options(&mut self,
+                               app_id: ApplicationId,
+                               options: LaunchOptions) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
         self.send_message(back::protocol::namespace::receiver(),
             back::protocol::MessageKind::Launch {
                 app_id: app_id,
-                request_id: 1,
-        })
+                request_id: request_id,
+                options: options,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Asks the receiver which of a set of applications it is able to run.
+    ///
+    /// Devices never block waiting for the receiver, so the answer can't
+    /// be returned from here. When it arrives, an `Event::AppAvailability`
+    /// event is raised with the ID of the request returned here, and a map
+    /// from each application ID to whether it is available.
+    ///
+    /// ```ignore
+    /// device.app_availability(&[apps::netflix(), apps::youtube()])?;
+    /// ```
+    pub fn app_availability(&mut self, app_ids: &[ApplicationId]) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.send_message(back::protocol::namespace::receiver(),
+            back::protocol::MessageKind::GetAppAvailability {
+                app_ids: app_ids.to_owned(),
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Asks the receiver to describe itself.
+    ///
+    /// When the answer arrives, the UUID and name in the `DeviceInfo` are
+    /// updated and an `Event::DeviceInfoUpdated` event is raised.
+    /// Returns the ID of the request.
+    pub fn device_info(&mut self) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.send_message(back::protocol::namespace::discovery(),
+            back::protocol::MessageKind::GetDeviceInfo {
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
     }
 
     /// Stop a running application.
@@ -78,30 +224,361 @@ impl Device
     /// Arguments:
     ///
     /// * `session_id` - The identifer of the session.
-    pub fn stop(&mut self, session_id: SessionId) -> Result<(), Error> {
+    ///
+    /// Returns the ID of the request.
+    pub fn stop(&mut self, session_id: SessionId) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
         self.send_message(back::protocol::namespace::receiver(),
             back::protocol::MessageKind::Stop {
                 session_id: session_id,
-        })
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
     }
 
     /// Sets the volume of the Cast device.
-    /// **NOTE**: This API is likely to change.
+    ///
+    /// This changes the volume of the whole device, as reported in
+    /// `Status::volume`. Use `Device::set_media_volume` to change the
+    /// volume of the media stream only.
+    ///
+    /// Levels outside of `VolumeLevel::min` and `VolumeLevel::max` are clamped.
+    /// Any fade in progress is cancelled.
+    ///
+    /// Fails with `ErrorKind::FixedVolume` if the last known status says the
+    /// volume of the device cannot be changed. Returns the ID of the request.
     pub fn set_volume(&mut self, level: Option<VolumeLevel>, muted: Option<bool>)
+        -> Result<RequestId, Error> {
+        if self.status.as_ref().map_or(false, |status| status.volume.is_fixed()) {
+            return Err(ErrorKind::FixedVolume.into());
+        }
+
+        self.fade = None;
+        self.send_volume(level.map(VolumeLevel::clamp), muted)
+    }
+
+    /// Turns the volume up by one step of `Volume::step_interval`.
+    pub fn volume_up(&mut self) -> Result<RequestId, Error> {
+        let level = self.current_volume()?.level_up();
+        self.set_volume(Some(level), None)
+    }
+
+    /// Turns the volume down by one step of `Volume::step_interval`.
+    pub fn volume_down(&mut self) -> Result<RequestId, Error> {
+        let level = self.current_volume()?.level_down();
+        self.set_volume(Some(level), None)
+    }
+
+    /// Gradually changes the volume from its current level to a target level.
+    ///
+    /// The fade is driven by `Device::update`, which must be called
+    /// regularly, for example every time the `mio` poll times out.
+    /// When the target is reached, an `Event::VolumeFadeFinished` event is raised.
+    ///
+    /// ```ignore
+    /// // Fade in over 30 seconds.
+    /// device.set_volume(Some(VolumeLevel::min()), Some(false))?;
+    /// device.fade_volume(VolumeLevel(0.6), Duration::from_secs(30))?;
+    /// ```
+    pub fn fade_volume(&mut self, target: VolumeLevel, duration: Duration)
         -> Result<(), Error> {
-        self.send_message(back::protocol::namespace::receiver(),
-            back::protocol::MessageKind::SetVolume {
-                level: level,
+        let volume = self.current_volume()?;
+        if volume.is_fixed() { return Err(ErrorKind::FixedVolume.into()) };
+
+        self.fade = Some(Fade::new(volume.level, target, Instant::now(), duration));
+        self.update()
+    }
+
+    /// Cancels the volume fade in progress, leaving the volume where it is.
+    pub fn cancel_fade(&mut self) {
+        self.fade = None;
+    }
+
+    /// Performs any work that depends on time passing, such as volume fades.
+    ///
+    /// Messages are also sent and received here for devices connected
+    /// with `Device::connect_over`.
+    pub fn update(&mut self) -> Result<(), Error> {
+        self.connection.update()?;
+        self.process_incoming()?;
+
+        let now = Instant::now();
+
+        let (level, finished) = match self.fade {
+            Some(ref mut fade) => (fade.next_level(now), fade.is_finished(now)),
+            None => return Ok(()),
+        };
+
+        if finished {
+            self.fade = None;
+        }
+
+        if let Some(level) = level {
+            self.send_volume(Some(level), None)?;
+        }
+
+        if finished {
+            self.add_event(Event::VolumeFadeFinished);
+        }
+
+        Ok(())
+    }
+
+    /// Asks a media application for the status of its media sessions.
+    ///
+    /// When the answer arrives, an `Event::MediaStatusUpdated` event is raised
+    /// and the status can be read with `Device::media_sessions`.
+    /// Returns the ID of the request.
+    ///
+    /// Arguments:
+    ///
+    /// * `transport_id` - The transport ID of the running application,
+    ///   from `Application::transport_id`.
+    pub fn update_media_status(&mut self, transport_id: EndpointName) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.connect_endpoint(transport_id.clone())?;
+        self.send_message_to(transport_id, back::protocol::namespace::media(),
+            back::protocol::MessageKind::MediaGetStatus {
+                media_session_id: None,
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Loads media into a media application, such as the Default Media Receiver.
+    ///
+    /// Arguments:
+    ///
+    /// * `transport_id` - The transport ID of the running application.
+    /// * `media` - Information about the media, such as `contentId`,
+    ///   `contentType`, `streamType` and `duration`.
+    pub fn load_media(&mut self,
+                      transport_id: EndpointName,
+                      media: json::JsonValue,
+                      autoplay: bool) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.connect_endpoint(transport_id.clone())?;
+        self.send_message_to(transport_id, back::protocol::namespace::media(),
+            back::protocol::MessageKind::MediaLoad {
+                media: media,
+                autoplay: Some(autoplay),
+                current_time: None,
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Resumes playing paused media.
+    pub fn play(&mut self) -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaPlay { media_session_id: media_session_id, request_id: request_id }
+        })
+    }
+
+    /// Pauses the media.
+    pub fn pause(&mut self) -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaPause { media_session_id: media_session_id, request_id: request_id }
+        })
+    }
+
+    /// Stops playing the media, leaving the application running.
+    pub fn stop_media(&mut self) -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaStop { media_session_id: media_session_id, request_id: request_id }
+        })
+    }
+
+    /// Sets the volume of the media stream that is being played.
+    ///
+    /// Unlike `Device::set_volume`, this leaves the volume of the device
+    /// alone. The new volume is reported in `MediaStatus::volume`.
+    ///
+    /// Fails with `ErrorKind::NoMediaSession` if no media status has been received.
+    pub fn set_media_volume(&mut self, level: Option<VolumeLevel>, muted: Option<bool>)
+        -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaSetVolume {
+                media_session_id: media_session_id,
+                level: level.map(VolumeLevel::clamp),
                 muted: muted,
+                request_id: request_id,
+            }
+        })
+    }
+
+    /// Changes the speed the media is played at.
+    ///
+    /// A rate of `1.0` is normal speed, `1.5` plays half again as fast.
+    pub fn set_playback_rate(&mut self, playback_rate: f32) -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaSetPlaybackRate {
+                media_session_id: media_session_id,
+                playback_rate: playback_rate,
+                request_id: request_id,
+            }
+        })
+    }
+
+    /// Seeks to a position in the media.
+    ///
+    /// ```ignore
+    /// // Jump back 30 seconds and keep playing.
+    /// device.seek(SeekPosition::Relative(-30.0), Some(ResumeState::PlaybackStart))?;
+    /// ```
+    pub fn seek(&mut self, position: SeekPosition, resume_state: Option<ResumeState>)
+        -> Result<RequestId, Error> {
+        self.send_media_request(|media_session_id, request_id| {
+            back::protocol::MessageKind::MediaSeek {
+                media_session_id: media_session_id,
+                position: position,
+                resume_state: resume_state,
+                request_id: request_id,
+            }
         })
     }
 
+    /// Seeks to the live edge of a live stream and starts playing.
+    ///
+    /// Fails with `ErrorKind::NotLive` if the media is not a live stream.
+    pub fn seek_to_live_edge(&mut self) -> Result<RequestId, Error> {
+        let live_edge = self.media_sessions.first()
+            .and_then(|session| session.status.live_seekable_range)
+            .and_then(|range| range.end);
+
+        match live_edge {
+            Some(live_edge) => self.seek(SeekPosition::Absolute(live_edge), Some(ResumeState::PlaybackStart)),
+            None => Err(ErrorKind::NotLive.into()),
+        }
+    }
+
+    /// Asks a Cast audio group for its members.
+    ///
+    /// When the answer arrives, an `Event::GroupUpdated` event is raised
+    /// and the members can be read with `Device::group_members`.
+    /// Returns the ID of the request.
+    pub fn update_group_status(&mut self) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.send_message(back::protocol::namespace::multizone(),
+            back::protocol::MessageKind::MultizoneGetStatus {
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Sets the volume of a single speaker within a Cast audio group.
+    ///
+    /// Arguments:
+    ///
+    /// * `device_id` - The identifier of the speaker, from `Member::device_id`.
+    pub fn set_member_volume(&mut self,
+                             device_id: &str,
+                             level: Option<VolumeLevel>,
+                             muted: Option<bool>) -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.send_message(back::protocol::namespace::multizone(),
+            back::protocol::MessageKind::MultizoneSetVolume {
+                device_id: device_id.to_owned(),
+                level: level,
+                muted: muted,
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Registers a custom namespace.
+    ///
+    /// Messages received on a registered namespace are raised as
+    /// `Event::NamespaceMessage` events.
+    pub fn register_namespace(&mut self, namespace: Namespace) {
+        self.namespaces.insert(namespace);
+    }
+
+    /// Unregisters a custom namespace.
+    pub fn unregister_namespace(&mut self, namespace: &Namespace) {
+        self.namespaces.remove(namespace);
+    }
+
+    /// Sends a message over a custom namespace.
+    ///
+    /// The destination is usually the transport ID of a running application.
+    /// A virtual connection to it will be established if there isn't one already.
+    pub fn send_payload(&mut self,
+                        destination: EndpointName,
+                        namespace: Namespace,
+                        payload: Payload) -> Result<(), Error> {
+        self.connect_endpoint(destination.clone())?;
+        self.send_message_to(destination, namespace,
+                             back::protocol::MessageKind::Custom { payload: payload })
+    }
+
+    /// Sends a JSON message over a custom namespace.
+    pub fn send_json(&mut self,
+                     destination: EndpointName,
+                     namespace: Namespace,
+                     payload: json::JsonValue) -> Result<(), Error> {
+        self.send_payload(destination, namespace, Payload::Utf8(payload))
+    }
+
+    /// Sends a binary message over a custom namespace.
+    pub fn send_binary(&mut self,
+                       destination: EndpointName,
+                       namespace: Namespace,
+                       payload: Vec<u8>) -> Result<(), Error> {
+        self.send_payload(destination, namespace, Payload::Binary(payload))
+    }
+
+    /// Establishes a virtual connection to an endpoint on the device.
+    ///
+    /// Does nothing if we are already connected to it.
+    pub fn connect_endpoint(&mut self, endpoint: EndpointName) -> Result<(), Error> {
+        if self.connected_endpoints.contains(&endpoint) { return Ok(()) };
+
+        self.send_message_to(endpoint.clone(),
+                             back::protocol::namespace::connection(),
+                             back::protocol::MessageKind::Connect)?;
+        self.connected_endpoints.insert(endpoint);
+        Ok(())
+    }
+
     /// Handle an IO event.
     pub fn handle_io(&mut self, event: mio::Event) -> Result<(), Error> {
         self.connection.handle_event(event)?;
         self.process_incoming()
     }
 
+    /// Records every packet sent to and received from the device.
+    ///
+    /// The recording is written as JSON lines, and can be played back
+    /// with a `recording::Replayer`.
+    pub fn record<W>(&mut self, log: W) where W: Write + Send + 'static {
+        self.connection.record(Recorder::new(log));
+    }
+
+    /// Stops recording packets.
+    pub fn stop_recording(&mut self) {
+        self.connection.stop_recording();
+    }
+
+    /// Handles a packet as if it had just been received from the device.
+    ///
+    /// This is used to play back recordings on a device created with
+    /// `Device::replaying`. The packet is ignored by any other device.
+    pub fn replay_packet(&mut self, packet: RawPacket) -> Result<(), Error> {
+        self.connection.feed(packet);
+        self.process_incoming()
+    }
+
     /// Consumes all of the events that have occurred on this device.
     pub fn events(&mut self) -> VecDeque<Event> {
         std::mem::replace(&mut self.event_queue, VecDeque::new())
@@ -113,13 +590,73 @@ impl Device
     /// Get the current status of the receiver.
     pub fn status(&self) -> Option<&Status> { self.status.as_ref() }
 
-    /// Sends a message.
+    /// Gets the description the receiver gave of itself, if it has been requested.
+    pub fn description(&self) -> Option<&DeviceDescription> { self.description.as_ref() }
+
+    /// Gets the media sessions of the running application.
+    ///
+    /// Use `MediaSession::estimated_position` to find out how far into
+    /// the media the receiver is now.
+    pub fn media_sessions(&self) -> &[MediaSession] { &self.media_sessions }
+
+    /// Gets the speakers in the Cast audio group, if the group status is known.
+    pub fn group_members(&self) -> Option<&[Member]> {
+        self.group.as_ref().map(|group| &group.members[..])
+    }
+
+    /// Sends a request on the media namespace for the current media session.
+    fn send_media_request<F>(&mut self, build_kind: F) -> Result<RequestId, Error>
+        where F: FnOnce(MediaSessionId, RequestId) -> back::protocol::MessageKind {
+        let (media_endpoint, media_session_id) = match (self.media_endpoint.clone(), self.media_sessions.first()) {
+            (Some(endpoint), Some(session)) => (endpoint, session.status.media_session_id),
+            _ => return Err(ErrorKind::NoMediaSession.into()),
+        };
+
+        let request_id = self.create_request_id();
+
+        self.send_message_to(media_endpoint, back::protocol::namespace::media(),
+                             build_kind(media_session_id, request_id))?;
+        Ok(request_id)
+    }
+
+    /// Gets the last known volume of the device.
+    fn current_volume(&self) -> Result<Volume, Error> {
+        match self.status {
+            Some(ref status) => Ok(status.volume.clone()),
+            None => Err(ErrorKind::StatusUnknown.into()),
+        }
+    }
+
+    /// Sends a `SET_VOLUME` message to the receiver.
+    fn send_volume(&mut self, level: Option<VolumeLevel>, muted: Option<bool>)
+        -> Result<RequestId, Error> {
+        let request_id = self.create_request_id();
+
+        self.send_message(back::protocol::namespace::receiver(),
+            back::protocol::MessageKind::SetVolume {
+                level: level,
+                muted: muted,
+                request_id: request_id,
+        })?;
+
+        Ok(request_id)
+    }
+
+    /// Sends a message to the receiver.
     fn send_message(&mut self,
-                    namespace: back::protocol::Namespace,
+                    namespace: Namespace,
                     kind: back::protocol::MessageKind) -> Result<(), Error> {
+        self.send_message_to(EndpointName(RECEIVER_ID.to_owned()), namespace, kind)
+    }
+
+    /// Sends a message to an endpoint on the device.
+    fn send_message_to(&mut self,
+                       destination: EndpointName,
+                       namespace: Namespace,
+                       kind: back::protocol::MessageKind) -> Result<(), Error> {
         self.connection.send(&back::protocol::Message {
-            source: back::protocol::EndpointName(SENDER_ID.to_owned()),
-            destination: back::protocol::EndpointName(RECEIVER_ID.to_owned()),
+            source: EndpointName(SENDER_ID.to_owned()),
+            destination: destination,
             namespace: namespace,
             kind: kind,
         })
@@ -127,29 +664,165 @@ impl Device
 
     /// Process all incoming messages.
     fn process_incoming(&mut self) -> Result<(), Error> {
-        for message in self.connection.receive()? {
-            match message.kind {
-                back::protocol::MessageKind::Ping => {
-                    self.connection.send(&back::protocol::Message {
-                        source: message.destination.clone(),
-                        destination: message.source.clone(),
-                        namespace: message.namespace.clone(),
-                        kind: back::protocol::MessageKind::Pong,
-                    }).expect("failed to send PONG");
-                },
-                back::protocol::MessageKind::ReceiverStatus(status) => {
-                    self.status = Some(status);
-                    self.add_event(Event::StatusUpdated);
-                },
-                msg => {
-                    println!("received unimplemented message: {:?}", msg);
-                },
+        for message in self.connection.receive() {
+            match message {
+                Ok(message) => self.handle_message(message)?,
+                Err(e) => self.add_event(Event::InvalidMessage { reason: e.to_string() }),
             }
         }
 
         Ok(())
     }
 
+    /// Handles a message that was received from the device.
+    fn handle_message(&mut self, message: back::protocol::Message) -> Result<(), Error> {
+        if let Some((request_id, error)) = message.kind.request_error() {
+            self.add_event(Event::RequestFailed { request_id: request_id, error: error });
+            return Ok(());
+        }
+
+        match message.kind {
+            back::protocol::MessageKind::Ping => {
+                self.connection.send(&back::protocol::Message {
+                    source: message.destination.clone(),
+                    destination: message.source.clone(),
+                    namespace: message.namespace.clone(),
+                    kind: back::protocol::MessageKind::Pong,
+                })?;
+            },
+            back::protocol::MessageKind::ReceiverStatus { ref status, .. } => {
+                self.update_receiver_status(status.clone());
+            },
+            back::protocol::MessageKind::AppAvailability { ref availability, request_id } => {
+                self.add_event(Event::AppAvailability {
+                    request_id: request_id,
+                    availability: availability.clone(),
+                });
+            },
+            back::protocol::MessageKind::DeviceInfo { ref description, .. } => {
+                self.update_description(description.clone());
+            },
+            back::protocol::MessageKind::MediaStatus { ref statuses, .. } => {
+                self.update_media_status_from(message.source.clone(), statuses.clone());
+            },
+            back::protocol::MessageKind::MultizoneStatus { ref status, .. } => {
+                self.group = Some(status.clone());
+                self.add_event(Event::GroupUpdated);
+            },
+            back::protocol::MessageKind::MemberAdded(ref member) |
+                back::protocol::MessageKind::MemberUpdated(ref member) => {
+                self.update_member(member.clone());
+            },
+            back::protocol::MessageKind::MemberRemoved { ref device_id } => {
+                if let Some(ref mut group) = self.group {
+                    group.members.retain(|member| member.device_id != *device_id);
+                }
+
+                self.add_event(Event::MemberLeft { device_id: device_id.clone() });
+            },
+            back::protocol::MessageKind::Close => {
+                // The endpoint has closed our virtual connection.
+                self.connected_endpoints.remove(&message.source);
+                self.add_event(Event::MessageReceived(message.clone()));
+            },
+            back::protocol::MessageKind::Custom { ref payload }
+                if self.namespaces.contains(&message.namespace) => {
+                self.add_event(Event::NamespaceMessage {
+                    namespace: message.namespace.clone(),
+                    source: message.source.clone(),
+                    payload: payload.clone(),
+                });
+            },
+            _ => {
+                self.add_event(Event::MessageReceived(message.clone()));
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Generates a unique request ID.
+    fn create_request_id(&mut self) -> RequestId {
+        let request_id = self.request_id_accumulator;
+        self.request_id_accumulator += 1;
+        request_id
+    }
+
+    /// Records a new receiver status, raising events for anything that changed.
+    fn update_receiver_status(&mut self, status: Status) {
+        let (was_active_input, was_stand_by) = match self.status {
+            Some(ref old_status) => (old_status.is_active_input, old_status.is_stand_by),
+            None => (None, None),
+        };
+
+        if let Some(is_active_input) = status.is_active_input {
+            if was_active_input != Some(is_active_input) {
+                self.add_event(Event::ActiveInputChanged { is_active_input: is_active_input });
+            }
+        }
+
+        if let Some(is_stand_by) = status.is_stand_by {
+            if was_stand_by != Some(is_stand_by) {
+                self.add_event(Event::StandbyChanged { is_stand_by: is_stand_by });
+            }
+        }
+
+        self.status = Some(status);
+        self.add_event(Event::StatusUpdated);
+    }
+
+    /// Records a new media status.
+    fn update_media_status_from(&mut self, source: EndpointName, statuses: Vec<MediaStatus>) {
+        let mut statuses = statuses;
+
+        // Receivers only send the media information when it changes.
+        for status in statuses.iter_mut().filter(|status| status.media.is_none()) {
+            status.media = self.media_sessions.iter()
+                .find(|session| session.status.media_session_id == status.media_session_id)
+                .and_then(|session| session.status.media.clone());
+        }
+
+        let received_at = Instant::now();
+        self.media_sessions = statuses.into_iter().map(|status| MediaSession::new(status, received_at)).collect();
+        self.media_endpoint = Some(source);
+        self.add_event(Event::MediaStatusUpdated);
+    }
+
+    /// Adds a speaker to the group or updates an existing one.
+    fn update_member(&mut self, member: Member) {
+        let group = self.group.get_or_insert(GroupStatus {
+            members: Vec::new(),
+            is_multichannel: None,
+        });
+
+        let existing_position = group.members.iter().position(|m| m.device_id == member.device_id);
+
+        let event = match existing_position {
+            Some(position) => {
+                group.members[position] = member.clone();
+                Event::MemberUpdated(member)
+            },
+            None => {
+                group.members.push(member.clone());
+                Event::MemberJoined(member)
+            },
+        };
+
+        self.add_event(event);
+    }
+
+    /// Records the description the receiver gave of itself.
+    fn update_description(&mut self, description: DeviceDescription) {
+        // Devices give their ID as a UUID without dashes.
+        if let Ok(uuid) = Uuid::parse_str(&description.device_id) {
+            self.info.uuid = uuid;
+        }
+
+        self.info.name = Some(description.friendly_name.clone());
+        self.description = Some(description);
+        self.add_event(Event::DeviceInfoUpdated);
+    }
+
     fn add_event(&mut self, event: Event) {
         self.event_queue.push_back(event);
 
@@ -158,3 +831,308 @@ impl Device
         }
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::{SENDER_ID, RECEIVER_ID};
+    use {Device, DeviceInfo, Event, Status, Volume, VolumeLevel, Namespace, EndpointName, Payload};
+    use back::net::{pipe, Pipe};
+    use back::net::transport::{Reader, RawPacket, write_packet};
+    use back::protocol::{namespace, Message, MessageKind, DeviceDescription, Member, MemberVolume,
+                         LaunchErrorReason};
+    use {apps, RequestError};
+
+    use std::net::Ipv4Addr;
+
+    use uuid::Uuid;
+
+    fn info() -> DeviceInfo {
+        DeviceInfo { ip_addr: Ipv4Addr::new(127, 0, 0, 1), port: 8009, uuid: Uuid::nil(), name: None }
+    }
+
+    fn status() -> Status {
+        Status {
+            volume: Volume {
+                control_type: "attenuation".to_owned(),
+                level: VolumeLevel(0.5),
+                muted: false,
+                step_interval: VolumeLevel(0.05),
+            },
+            applications: Vec::new(),
+            is_active_input: None,
+            is_stand_by: None,
+            user_eq: None,
+        }
+    }
+
+    /// Builds a packet sent from the receiver to the device.
+    fn from_receiver(namespace: Namespace, kind: MessageKind) -> RawPacket {
+        Message {
+            source: EndpointName(RECEIVER_ID.to_owned()),
+            destination: EndpointName(SENDER_ID.to_owned()),
+            namespace: namespace,
+            kind: kind,
+        }.as_raw_bytes().unwrap()
+    }
+
+    /// Reads the messages a device has sent.
+    fn sent(device: &mut Device, receiver: &mut Pipe) -> Vec<Message> {
+        device.update().unwrap();
+
+        let mut packets = Vec::new();
+        Reader::new().read(receiver, &mut packets).unwrap();
+        packets.iter().map(|packet| Message::from_raw_bytes(packet).unwrap()).collect()
+    }
+
+    fn player() -> Namespace { Namespace("urn:x-cast:com.example.player".to_owned()) }
+
+    fn track(number: u32) -> ::json::JsonValue {
+        let mut payload = ::json::JsonValue::new_object();
+        payload["track"] = number.into();
+        payload
+    }
+
+    /// Gives a packet to a replaying device, returning the events it raised.
+    fn replay(device: &mut Device, namespace: Namespace, kind: MessageKind) -> Vec<Event> {
+        device.replay_packet(from_receiver(namespace, kind)).unwrap();
+        device.events().into_iter().collect()
+    }
+
+    #[test]
+    fn devices_talk_over_any_stream() {
+        let (stream, mut receiver) = pipe();
+        let mut device = Device::connect_over(info(), stream).unwrap();
+        device.update_status().unwrap();
+        device.update().unwrap();
+
+        let mut packets = Vec::new();
+        Reader::new().read(&mut receiver, &mut packets).unwrap();
+        let kinds: Vec<_> = packets.iter().map(|packet| Message::from_raw_bytes(packet).unwrap().kind).collect();
+        assert_eq!(kinds, vec![MessageKind::Connect, MessageKind::GetStatus { request_id: 1 }]);
+
+        write_packet(&mut receiver, &from_receiver(namespace::receiver(), MessageKind::ReceiverStatus { status: status(), request_id: 0 })).unwrap();
+        device.update().unwrap();
+
+        let events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::StatusUpdated => (),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(device.status(), Some(&status()));
+    }
+
+    #[test]
+    fn launch_errors_name_the_launch() {
+        let (stream, mut receiver) = pipe();
+        let mut device = Device::connect_over(info(), stream).unwrap();
+        let request_id = device.launch(apps::youtube()).unwrap();
+
+        write_packet(&mut receiver, &from_receiver(namespace::receiver(), MessageKind::LaunchError {
+            reason: LaunchErrorReason::NotFound,
+            request_id: request_id,
+        })).unwrap();
+        device.update().unwrap();
+
+        let events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::RequestFailed { request_id: id, error: RequestError::LaunchFailed(LaunchErrorReason::NotFound) } => {
+                assert_eq!(id, request_id)
+            },
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn standby_and_active_input_changes_are_raised() {
+        let mut device = Device::replaying(info());
+        let mut on_tv = status();
+        on_tv.is_active_input = Some(true);
+        on_tv.is_stand_by = Some(false);
+
+        let events = replay(&mut device, namespace::receiver(), MessageKind::ReceiverStatus { status: on_tv.clone(), request_id: 0 });
+        assert_eq!(events.len(), 3, "unexpected events: {:?}", events);
+        match (&events[0], &events[1], &events[2]) {
+            (&Event::ActiveInputChanged { is_active_input: true },
+             &Event::StandbyChanged { is_stand_by: false },
+             &Event::StatusUpdated) => (),
+            _ => panic!("unexpected events: {:?}", events),
+        }
+
+        // Nothing changed, so only the status is updated.
+        let events = replay(&mut device, namespace::receiver(), MessageKind::ReceiverStatus { status: on_tv.clone(), request_id: 0 });
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+
+        let mut in_standby = on_tv.clone();
+        in_standby.is_stand_by = Some(true);
+
+        let events = replay(&mut device, namespace::receiver(), MessageKind::ReceiverStatus { status: in_standby, request_id: 0 });
+        assert_eq!(events.len(), 2, "unexpected events: {:?}", events);
+        match (&events[0], &events[1]) {
+            (&Event::StandbyChanged { is_stand_by: true }, &Event::StatusUpdated) => (),
+            _ => panic!("unexpected events: {:?}", events),
+        }
+    }
+
+    #[test]
+    fn device_info_describes_the_device() {
+        let mut device = Device::replaying(info());
+        let description = DeviceDescription {
+            device_id: "4a7a4b1d9c3f4e0e8d2b2c0c7b9e5a31".to_owned(),
+            friendly_name: "Kitchen".to_owned(),
+            model: Some("Chromecast".to_owned()),
+            capabilities: None,
+            control_notifications: None,
+            icon_url: None,
+            receiver_metrics_id: None,
+        };
+
+        let events = replay(&mut device, namespace::discovery(), MessageKind::DeviceInfo {
+            description: description.clone(),
+            request_id: 1,
+        });
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::DeviceInfoUpdated => (),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+
+        assert_eq!(device.info().name, Some("Kitchen".to_owned()));
+        assert_eq!(device.info().uuid, "4a7a4b1d-9c3f-4e0e-8d2b-2c0c7b9e5a31".parse().unwrap());
+        assert_eq!(device.description(), Some(&description));
+    }
+
+    #[test]
+    fn group_members_join_and_leave() {
+        let mut device = Device::replaying(info());
+        let mut member = Member {
+            device_id: "kitchen".to_owned(),
+            name: "Kitchen speaker".to_owned(),
+            capabilities: None,
+            volume: MemberVolume { level: VolumeLevel(0.5), muted: false },
+        };
+
+        let events = replay(&mut device, namespace::multizone(), MessageKind::MemberAdded(member.clone()));
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::MemberJoined(ref joined) => assert_eq!(*joined, member),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(device.group_members(), Some(&[member.clone()][..]));
+
+        member.volume.muted = true;
+        let events = replay(&mut device, namespace::multizone(), MessageKind::MemberUpdated(member.clone()));
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::MemberUpdated(ref updated) => assert_eq!(*updated, member),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(device.group_members(), Some(&[member.clone()][..]));
+
+        let events = replay(&mut device, namespace::multizone(), MessageKind::MemberRemoved {
+            device_id: "kitchen".to_owned(),
+        });
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::MemberLeft { ref device_id } => assert_eq!(device_id, "kitchen"),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(device.group_members(), Some(&[][..]));
+    }
+
+    #[test]
+    fn custom_messages_connect_to_the_application_first() {
+        let (stream, mut receiver) = pipe();
+        let mut device = Device::connect_over(info(), stream).unwrap();
+        sent(&mut device, &mut receiver);
+
+        let app = EndpointName("web-5".to_owned());
+        device.send_json(app.clone(), player(), track(3)).unwrap();
+        device.send_json(app.clone(), player(), track(4)).unwrap();
+
+        let messages = sent(&mut device, &mut receiver);
+        assert_eq!(messages.len(), 3, "unexpected messages: {:?}", messages);
+        assert_eq!((&messages[0].destination, &messages[0].namespace, &messages[0].kind),
+                   (&app, &namespace::connection(), &MessageKind::Connect));
+
+        for (message, number) in messages[1..].iter().zip(vec![3, 4]) {
+            assert_eq!(message.destination, app);
+            assert_eq!(message.namespace, player());
+            assert_eq!(message.kind, MessageKind::Custom { payload: Payload::Utf8(track(number)) });
+        }
+    }
+
+    #[test]
+    fn registered_namespaces_raise_their_messages() {
+        let (stream, mut receiver) = pipe();
+        let mut device = Device::connect_over(info(), stream).unwrap();
+        let other = Namespace("urn:x-cast:com.example.other".to_owned());
+
+        let from_app = |namespace: Namespace| Message {
+            source: EndpointName("web-5".to_owned()),
+            destination: EndpointName(SENDER_ID.to_owned()),
+            namespace: namespace,
+            kind: MessageKind::Custom { payload: Payload::Utf8(track(3)) },
+        }.as_raw_bytes().unwrap();
+
+        device.register_namespace(player());
+        write_packet(&mut receiver, &from_app(player())).unwrap();
+        write_packet(&mut receiver, &from_app(other.clone())).unwrap();
+        device.update().unwrap();
+
+        let events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 2, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::NamespaceMessage { ref namespace, ref source, payload: Payload::Utf8(ref payload) } => {
+                assert_eq!(namespace, &player());
+                assert_eq!(source, &EndpointName("web-5".to_owned()));
+                assert_eq!(payload["track"], 3);
+            },
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        match events[1] {
+            Event::MessageReceived(ref message) => assert_eq!(message.namespace, other),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+
+        device.unregister_namespace(&player());
+        write_packet(&mut receiver, &from_app(player())).unwrap();
+        device.update().unwrap();
+
+        let events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 1, "unexpected events: {:?}", events);
+        match events[0] {
+            Event::MessageReceived(ref message) => assert_eq!(message.namespace, player()),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn invalid_messages_do_not_stop_the_others() {
+        let (stream, mut receiver) = pipe();
+        let mut device = Device::connect_over(info(), stream).unwrap();
+
+        let mut message = Message {
+            source: EndpointName(RECEIVER_ID.to_owned()),
+            destination: EndpointName(SENDER_ID.to_owned()),
+            namespace: namespace::receiver(),
+            kind: MessageKind::ReceiverStatus { status: status(), request_id: 0 },
+        }.as_wire_message();
+        message.set_payload_utf8("{\"type\":\"RECEIVER_STATUS\",\"status\":{}}".to_owned());
+        let invalid_status = ::protobuf::Message::write_to_bytes(&message).unwrap();
+
+        write_packet(&mut receiver, &invalid_status).unwrap();
+        write_packet(&mut receiver, &from_receiver(namespace::receiver(), MessageKind::ReceiverStatus { status: status(), request_id: 0 })).unwrap();
+        device.update().unwrap();
+
+        let events: Vec<_> = device.events().into_iter().collect();
+        assert_eq!(events.len(), 2, "unexpected events: {:?}", events);
+        match (&events[0], &events[1]) {
+            (&Event::InvalidMessage { ref reason }, &Event::StatusUpdated) => assert!(reason.contains("status.volume"), "{}", reason),
+            _ => panic!("unexpected events: {:?}", events),
+        }
+        assert_eq!(device.status(), Some(&status()));
+    }
+}
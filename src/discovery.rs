@@ -8,44 +8,169 @@ use std::time::Duration;
 
 use uuid::Uuid;
 
+/// The port that Cast devices listen on.
+pub const DEFAULT_PORT: u16 = 8009;
+
+/// The mDNS service that Cast devices advertise.
+pub const SERVICE_NAME: &'static str = "_googlecast._tcp.local";
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DeviceInfo
 {
     pub ip_addr: Ipv4Addr,
+    /// The port the device listens on, usually `DEFAULT_PORT`.
+    pub port: u16,
     pub uuid: Uuid,
+    /// The name the user has given the device, if known.
+    pub name: Option<String>,
+}
+
+impl DeviceInfo
+{
+    /// Creates information for a device that is only known by its IP address.
+    ///
+    /// The UUID and name can be filled in by calling `Device::device_info`
+    /// once connected.
+    pub fn from_ip_addr(ip_addr: Ipv4Addr) -> Self {
+        DeviceInfo {
+            ip_addr: ip_addr,
+            port: DEFAULT_PORT,
+            uuid: Uuid::nil(),
+            name: None,
+        }
+    }
 }
 
 /// Performs Cast discovery.
 pub fn run<F>(duration: Duration, mut f: F) -> Result<(), Error>
     where F: FnMut(DeviceInfo)  {
-    let mut discovered_addresses = Vec::new();
-
-    mdns::discover("_googlecast._tcp.local", Some(duration), |response| {
-        if response.records().next().is_none() { return };
-
-        let mut address = None;
-        let mut uuid_str = None;
-
-        for record in response.records() {
-            match record.kind {
-                mdns::RecordKind::A(ref addr) => {
-                    address = Some(addr.clone());
-                    uuid_str = Some(record.name.replace(".local", ""));
-                },
-                _ => (),
-            }
+    let mut discovered = Discovered::default();
+
+    mdns::discover(SERVICE_NAME, Some(duration), |response| {
+        let info = match device_info(&response) {
+            Some(info) => info,
+            None => return,
+        };
+
+        if discovered.is_new(&info) { f(info) };
+    })?;
+    Ok(())
+}
+
+/// The addresses of the devices found so far.
+///
+/// Devices answer every query, and announce themselves too, so the
+/// same device is usually heard from several times.
+#[derive(Debug, Default)]
+struct Discovered
+{
+    addresses: Vec<(Ipv4Addr, u16)>,
+}
+
+impl Discovered
+{
+    /// Remembers a device, checking whether it had not been found before.
+    fn is_new(&mut self, info: &DeviceInfo) -> bool {
+        let address = (info.ip_addr, info.port);
+        if self.addresses.contains(&address) { return false };
+
+        self.addresses.push(address);
+        true
+    }
+}
+
+/// Reads the information about a device out of an mDNS response.
+///
+/// Returns `None` for responses that are not from a Cast device, such as
+/// those with no address or with a host name that is not a UUID.
+fn device_info(response: &mdns::Response) -> Option<DeviceInfo> {
+    let mut address = None;
+    let mut uuid_str = None;
+    let mut port = DEFAULT_PORT;
+    let mut name = None;
+
+    for record in response.records() {
+        match record.kind {
+            mdns::RecordKind::A(ref addr) => {
+                address = Some(addr.clone());
+                uuid_str = Some(record.name.replace(".local", ""));
+            },
+            mdns::RecordKind::SRV { port: srv_port, .. } => port = srv_port,
+            // mdns doesn't decode TXT records, so their data is given
+            // to us as is. Other records it can't decode look nothing
+            // like `key=value` entries, and are passed over.
+            mdns::RecordKind::Unimplemented(ref data) => {
+                for (key, value) in txt_entries(data) {
+                    if key == "fn" { name = Some(value) };
+                }
+            },
+            _ => (),
         }
+    }
+
+    let uuid = match uuid_str.map(|uuid_str| uuid_str.parse()) {
+        Some(Ok(uuid)) => uuid,
+        _ => return None,
+    };
+
+    Some(DeviceInfo { ip_addr: address?, port: port, uuid: uuid, name: name })
+}
+
+/// Reads the `key=value` strings out of the data of a TXT record.
+///
+/// Each string is prefixed by its length.
+fn txt_entries(bytes: &[u8]) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut position = 0;
 
-        let address = address.unwrap();
-        let uuid = uuid_str.unwrap().parse().expect("invalid device UUID");
+    while position < bytes.len() {
+        let end = ::std::cmp::min(position + 1 + bytes[position] as usize, bytes.len());
+        let entry = String::from_utf8_lossy(&bytes[position + 1..end]);
 
-        // Do not discover the same Cast IP address more than once.
-        if !discovered_addresses.iter().any(|discovered_address| discovered_address == &address) {
-            f(DeviceInfo { ip_addr: address, uuid: uuid })
-        } else {
-            discovered_addresses.push(address);
+        if let Some(separator) = entry.find('=') {
+            entries.push((entry[..separator].to_owned(), entry[separator + 1..].to_owned()));
         }
 
-    })?;
-    Ok(())
+        position = end;
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{txt_entries, Discovered, DeviceInfo};
+
+    use std::net::Ipv4Addr;
+
+    fn device(ip_addr: Ipv4Addr, port: u16) -> DeviceInfo {
+        DeviceInfo { port: port, ..DeviceInfo::from_ip_addr(ip_addr) }
+    }
+
+    #[test]
+    fn reads_txt_entries() {
+        assert_eq!(txt_entries(b"\x06fn=Den\x04ca=5"), vec![
+            ("fn".to_owned(), "Den".to_owned()),
+            ("ca".to_owned(), "5".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn devices_are_only_reported_once() {
+        let mut discovered = Discovered::default();
+        let kitchen = Ipv4Addr::new(192, 168, 1, 20);
+
+        assert!(discovered.is_new(&device(kitchen, 8009)));
+        assert!(!discovered.is_new(&device(kitchen, 8009)));
+
+        // Speaker groups are served from another port of a member.
+        assert!(discovered.is_new(&device(kitchen, 42031)));
+        assert!(discovered.is_new(&device(Ipv4Addr::new(192, 168, 1, 21), 8009)));
+    }
+
+    #[test]
+    fn truncated_txt_entries_are_kept() {
+        assert_eq!(txt_entries(b"\x09fn=Den"), vec![("fn".to_owned(), "Den".to_owned())]);
+    }
 }
@@ -0,0 +1,423 @@
+//! An mDNS responder that makes a receiver discoverable.
+//!
+//! The responder answers queries for `_googlecast._tcp.local` with the
+//! records a real Cast device gives:
+//!
+//! * `PTR` from the service to the instance name of the device
+//! * `SRV` from the instance name to the host name and port
+//! * `TXT` with the `id`, `fn`, `md` and `ca` of the device
+//! * `A` from the host name to the IP address
+//!
+//! It is enough for `discovery::run` to find the device, without any
+//! other mDNS software running.
+
+use {DeviceInfo, Error};
+use discovery::SERVICE_NAME;
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{io, thread};
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use net2::UdpBuilder;
+
+/// The port mDNS is spoken over.
+pub const MDNS_PORT: u16 = 5353;
+
+/// The multicast group mDNS is spoken over.
+pub fn mdns_group() -> Ipv4Addr { Ipv4Addr::new(224, 0, 0, 251) }
+
+/// The model advertised in the `md` TXT entry.
+const MODEL: &'static str = "gcast emulator";
+/// The capabilities advertised in the `ca` TXT entry: video and audio out.
+const CAPABILITIES: u32 = 1 | 4;
+
+/// How long a host record may be cached for, in seconds.
+const HOST_TTL: u32 = 120;
+/// How long the service pointer may be cached for, in seconds.
+const SERVICE_TTL: u32 = 4500;
+
+/// How long the responder waits for a query before checking whether to stop.
+const POLL_INTERVAL_MILLIS: u64 = 100;
+/// The largest mDNS packet we read.
+const MAX_PACKET_SIZE: usize = 9000;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+const CLASS_IN: u16 = 1;
+/// Set on the class of records that only this device owns.
+const CACHE_FLUSH: u16 = 0x8000;
+/// Set on the class of questions that ask for a unicast answer.
+const UNICAST_RESPONSE: u16 = 0x8000;
+
+/// The flags of a response: this is an authoritative answer.
+const RESPONSE_FLAGS: u16 = 0x8400;
+/// Set in the flags of responses.
+const FLAG_RESPONSE: u16 = 0x8000;
+
+/// A running mDNS responder.
+///
+/// The responder stops when it is dropped.
+pub struct Advertiser
+{
+    stopping: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// The names and records given out for a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Advertisement
+{
+    /// The name of this instance of the service, such as
+    /// `gcast-0123456789abcdef0123456789abcdef._googlecast._tcp.local`.
+    pub instance_name: String,
+    /// The host name of the device, such as `01234567-89ab-cdef-0123-456789abcdef.local`.
+    pub host_name: String,
+    pub ip_addr: Ipv4Addr,
+    pub port: u16,
+    /// The `key=value` strings of the TXT record.
+    pub txt: Vec<String>,
+}
+
+/// A question from an mDNS query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Question
+{
+    name: String,
+    kind: u16,
+    unicast_response: bool,
+}
+
+impl Advertiser
+{
+    /// Starts answering queries for a device on the given interface.
+    ///
+    /// Use `Ipv4Addr::new(127, 0, 0, 1)` to only be discoverable from
+    /// the local machine.
+    pub fn start(device: &DeviceInfo, interface: Ipv4Addr) -> Result<Self, Error> {
+        let advertisement = Advertisement::new(device);
+
+        let socket = bind(interface)?;
+        socket.set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL_MILLIS)))?;
+
+        // Announce ourselves so that running discoveries notice straight
+        // away. Queries are still answered if there is no multicast route.
+        let group = SocketAddr::V4(SocketAddrV4::new(mdns_group(), MDNS_PORT));
+        let _ = socket.send_to(&advertisement.response(0), group);
+
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stopping = stopping.clone();
+
+            thread::spawn(move || {
+                while !stopping.load(Ordering::SeqCst) {
+                    // Errors from a single packet should not stop the responder.
+                    let _ = respond(&socket, &advertisement, group);
+                }
+            })
+        };
+
+        Ok(Advertiser {
+            stopping: stopping,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Advertiser
+{
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Advertisement
+{
+    /// Builds the records for a device.
+    pub fn new(device: &DeviceInfo) -> Self {
+        let id = device.uuid.simple().to_string();
+        let name = device.name.clone().unwrap_or_else(|| MODEL.to_owned());
+
+        Advertisement {
+            instance_name: format!("gcast-{}.{}", id, SERVICE_NAME),
+            host_name: format!("{}.local", device.uuid.hyphenated()),
+            ip_addr: device.ip_addr,
+            port: device.port,
+            txt: vec![
+                format!("id={}", id),
+                format!("fn={}", name),
+                format!("md={}", MODEL),
+                format!("ca={}", CAPABILITIES),
+            ],
+        }
+    }
+
+    /// Checks whether a question is about this device.
+    fn answers(&self, question: &Question) -> bool {
+        let name = question.name.to_lowercase();
+        let is_known_type = |kinds: &[u16]| question.kind == TYPE_ANY || kinds.contains(&question.kind);
+
+        (name == SERVICE_NAME.to_lowercase() && is_known_type(&[TYPE_PTR])) ||
+            (name == self.instance_name.to_lowercase() && is_known_type(&[TYPE_SRV, TYPE_TXT])) ||
+            (name == self.host_name.to_lowercase() && is_known_type(&[TYPE_A]))
+    }
+
+    /// Builds a response with the pointer to the device as the answer,
+    /// and everything needed to connect to it as additional records.
+    fn response(&self, id: u16) -> Vec<u8> {
+        let mut packet = Vec::new();
+
+        let txt: Vec<u8> = self.txt.iter().flat_map(|entry| {
+            Some(entry.len() as u8).into_iter().chain(entry.bytes())
+        }).collect();
+
+        let mut srv = Vec::new();
+        srv.write_u16::<BigEndian>(0).unwrap(); // priority
+        srv.write_u16::<BigEndian>(0).unwrap(); // weight
+        srv.write_u16::<BigEndian>(self.port).unwrap();
+        write_name(&mut srv, &self.host_name);
+
+        let mut ptr = Vec::new();
+        write_name(&mut ptr, &self.instance_name);
+
+        packet.write_u16::<BigEndian>(id).unwrap();
+        packet.write_u16::<BigEndian>(RESPONSE_FLAGS).unwrap();
+        packet.write_u16::<BigEndian>(0).unwrap(); // questions
+        packet.write_u16::<BigEndian>(1).unwrap(); // answers
+        packet.write_u16::<BigEndian>(0).unwrap(); // authorities
+        packet.write_u16::<BigEndian>(3).unwrap(); // additional records
+
+        write_record(&mut packet, SERVICE_NAME, TYPE_PTR, CLASS_IN, SERVICE_TTL, &ptr);
+        write_record(&mut packet, &self.instance_name, TYPE_SRV, CLASS_IN | CACHE_FLUSH, HOST_TTL, &srv);
+        write_record(&mut packet, &self.instance_name, TYPE_TXT, CLASS_IN | CACHE_FLUSH, HOST_TTL, &txt);
+        write_record(&mut packet, &self.host_name, TYPE_A, CLASS_IN | CACHE_FLUSH, HOST_TTL, &self.ip_addr.octets());
+
+        packet
+    }
+}
+
+/// Binds a socket to the mDNS port, sharing it with any other responders.
+fn bind(interface: Ipv4Addr) -> Result<UdpSocket, Error> {
+    let builder = UdpBuilder::new_v4()?;
+    builder.reuse_address(true)?;
+    reuse_port(&builder)?;
+
+    let socket = builder.bind((Ipv4Addr::new(0, 0, 0, 0), MDNS_PORT))?;
+    socket.join_multicast_v4(&mdns_group(), &interface)?;
+    socket.set_multicast_loop_v4(true)?;
+
+    Ok(socket)
+}
+
+#[cfg(unix)]
+fn reuse_port(builder: &UdpBuilder) -> io::Result<()> {
+    use net2::unix::UnixUdpBuilderExt;
+    builder.reuse_port(true).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn reuse_port(_: &UdpBuilder) -> io::Result<()> { Ok(()) }
+
+/// Waits for a query and answers it if it is about the device.
+fn respond(socket: &UdpSocket, advertisement: &Advertisement, group: SocketAddr) -> io::Result<()> {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+
+    let (size, source) = match socket.recv_from(&mut buffer) {
+        Ok(received) => received,
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                      e.kind() == io::ErrorKind::TimedOut => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let (id, questions) = match parse_query(&buffer[..size]) {
+        Some(query) => query,
+        None => return Ok(()),
+    };
+
+    let relevant: Vec<&Question> = questions.iter().filter(|question| advertisement.answers(question)).collect();
+    if relevant.is_empty() { return Ok(()) };
+
+    // Queries that do not come from the mDNS port are from simple
+    // resolvers that expect a direct, conventional DNS answer.
+    let is_legacy = source.port() != MDNS_PORT;
+
+    if is_legacy || relevant.iter().all(|question| question.unicast_response) {
+        socket.send_to(&advertisement.response(if is_legacy { id } else { 0 }), source)?;
+    } else {
+        socket.send_to(&advertisement.response(0), group)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the ID and questions out of a query, ignoring responses.
+fn parse_query(packet: &[u8]) -> Option<(u16, Vec<Question>)> {
+    if packet.len() < 12 { return None };
+
+    let id = BigEndian::read_u16(&packet[0..2]);
+    let flags = BigEndian::read_u16(&packet[2..4]);
+    let question_count = BigEndian::read_u16(&packet[4..6]);
+
+    if flags & FLAG_RESPONSE != 0 { return None };
+
+    let mut offset = 12;
+    let mut questions = Vec::new();
+
+    for _ in 0..question_count {
+        let (name, end) = read_name(packet, offset)?;
+        if packet.len() < end + 4 { return None };
+
+        let class = BigEndian::read_u16(&packet[end + 2..end + 4]);
+
+        questions.push(Question {
+            name: name,
+            kind: BigEndian::read_u16(&packet[end..end + 2]),
+            unicast_response: class & UNICAST_RESPONSE != 0,
+        });
+        offset = end + 4;
+    }
+
+    Some((id, questions))
+}
+
+/// Reads a possibly compressed name, returning it and the offset after it.
+fn read_name(packet: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut position = offset;
+    let mut end = None;
+
+    // Each pointer must go backwards, so this is enough to stop loops.
+    for _ in 0..packet.len() {
+        let length = *packet.get(position)? as usize;
+
+        if length & 0xc0 == 0xc0 {
+            let target = BigEndian::read_u16(packet.get(position..position + 2)?) as usize & 0x3fff;
+            if target >= position { return None };
+
+            if end.is_none() { end = Some(position + 2) };
+            position = target;
+        } else if length == 0 {
+            return Some((labels.join("."), end.unwrap_or(position + 1)));
+        } else {
+            let label = packet.get(position + 1..position + 1 + length)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            position += 1 + length;
+        }
+    }
+
+    None
+}
+
+fn write_name(packet: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+
+    packet.push(0);
+}
+
+fn write_record(packet: &mut Vec<u8>, name: &str, kind: u16, class: u16, ttl: u32, data: &[u8]) {
+    write_name(packet, name);
+    packet.write_u16::<BigEndian>(kind).unwrap();
+    packet.write_u16::<BigEndian>(class).unwrap();
+    packet.write_u32::<BigEndian>(ttl).unwrap();
+    packet.write_u16::<BigEndian>(data.len() as u16).unwrap();
+    packet.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use super::{Question, TYPE_PTR, TYPE_A, TYPE_SRV, UNICAST_RESPONSE};
+    use DeviceInfo;
+    use discovery::SERVICE_NAME;
+
+    use std::net::Ipv4Addr;
+
+    use byteorder::{BigEndian, WriteBytesExt};
+    use uuid::Uuid;
+
+    fn device() -> DeviceInfo {
+        DeviceInfo {
+            ip_addr: Ipv4Addr::new(127, 0, 0, 1),
+            port: 40000,
+            uuid: Uuid::parse_str("0123456789abcdef0123456789abcdef").unwrap(),
+            name: Some("Living Room".to_owned()),
+        }
+    }
+
+    fn query(name: &str, kind: u16, class: u16) -> Vec<u8> {
+        let mut packet = vec![0, 7, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        super::write_name(&mut packet, name);
+        packet.write_u16::<BigEndian>(kind).unwrap();
+        packet.write_u16::<BigEndian>(class).unwrap();
+        packet
+    }
+
+    #[test]
+    fn txt_records_describe_the_device() {
+        assert_eq!(Advertisement::new(&device()).txt, vec![
+            "id=0123456789abcdef0123456789abcdef".to_owned(),
+            "fn=Living Room".to_owned(),
+            "md=gcast emulator".to_owned(),
+            "ca=5".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn reads_queries() {
+        let packet = query(SERVICE_NAME, TYPE_PTR, 1 | UNICAST_RESPONSE);
+
+        assert_eq!(super::parse_query(&packet), Some((7, vec![Question {
+            name: SERVICE_NAME.to_owned(),
+            kind: TYPE_PTR,
+            unicast_response: true,
+        }])));
+    }
+
+    #[test]
+    fn reads_compressed_names() {
+        let mut packet = vec![0; 12];
+        super::write_name(&mut packet, "device.local");
+        packet.extend_from_slice(&[3, b'f', b'o', b'o', 0xc0, 12 + 7]);
+
+        assert_eq!(super::read_name(&packet, 12 + 14), Some(("foo.local".to_owned(), 12 + 20)));
+    }
+
+    #[test]
+    fn pointer_loops_are_refused() {
+        let packet = vec![0xc0, 0];
+        assert_eq!(super::read_name(&packet, 0), None);
+    }
+
+    #[test]
+    fn answers_questions_about_the_device() {
+        let advertisement = Advertisement::new(&device());
+        let question = |name: &str, kind| Question { name: name.to_owned(), kind: kind, unicast_response: false };
+
+        assert!(advertisement.answers(&question("_GoogleCast._tcp.local", TYPE_PTR)));
+        assert!(advertisement.answers(&question(&advertisement.instance_name, TYPE_SRV)));
+        assert!(advertisement.answers(&question(&advertisement.host_name, TYPE_A)));
+        assert!(!advertisement.answers(&question("_airplay._tcp.local", TYPE_PTR)));
+        assert!(!advertisement.answers(&question(&advertisement.host_name, TYPE_PTR)));
+    }
+
+    #[test]
+    fn responses_are_not_mistaken_for_queries() {
+        let advertisement = Advertisement::new(&device());
+        assert_eq!(super::parse_query(&advertisement.response(0)), None);
+    }
+}
@@ -0,0 +1,252 @@
+//! Scripted faults for testing how senders cope with misbehaving receivers.
+
+use back::net::transport;
+use back::protocol::{Message, MessageKind, Payload, EndpointName};
+use receiver::{Interceptor, Outcome};
+use Error;
+
+use std::io::prelude::*;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::thread;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use json;
+
+/// Something that goes wrong on an emulated receiver.
+///
+/// Faults are added with `Emulator::inject`. Faults that change how the
+/// receiver answers last until `Emulator::clear_faults` is called. The
+/// others happen once, on the first connection to notice them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault
+{
+    /// Stop answering `PING` messages, as if the receiver hung.
+    IgnorePings,
+    /// Wait before sending each response.
+    DelayResponses(Duration),
+    /// Close the connection after sending part of a size prefix.
+    CloseMidFrame,
+    /// Send a size prefix larger than `transport::MAX_PACKET_SIZE`.
+    OversizedFrame,
+    /// Send a size prefix and only half of the packet, then close the connection.
+    TruncatedFrame,
+    /// Send a receiver message with a type that no sender knows about.
+    UnknownMessageType(String),
+    /// Send a `RECEIVER_STATUS` message that is missing its volume.
+    MalformedStatus,
+}
+
+/// The faults injected into an emulator.
+///
+/// The faults are applied to every connection by intercepting its traffic.
+#[derive(Debug, Default)]
+pub struct Faults
+{
+    faults: Mutex<Vec<Fault>>,
+}
+
+impl Fault
+{
+    /// Checks whether the fault changes every response rather than happening once.
+    pub fn is_persistent(&self) -> bool {
+        match *self {
+            Fault::IgnorePings | Fault::DelayResponses(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Makes a fault that happens once happen on a stream.
+    pub fn perform(&self, write: &mut Write) -> Result<Outcome, Error> {
+        match *self {
+            Fault::IgnorePings | Fault::DelayResponses(..) => Ok(Outcome::Continue),
+            Fault::CloseMidFrame => {
+                write.write_all(&[0, 0])?;
+                write.flush()?;
+                Ok(Outcome::Close)
+            },
+            Fault::OversizedFrame => {
+                write.write_u32::<BigEndian>(transport::MAX_PACKET_SIZE as u32 + 1)?;
+                write.flush()?;
+                Ok(Outcome::Continue)
+            },
+            Fault::TruncatedFrame => {
+                let packet = receiver_message(MessageKind::GetStatus { request_id: 0 }).as_raw_bytes()?;
+
+                write.write_u32::<BigEndian>(packet.len() as u32)?;
+                write.write_all(&packet[..packet.len() / 2])?;
+                write.flush()?;
+                Ok(Outcome::Close)
+            },
+            Fault::UnknownMessageType(ref type_name) => {
+                let message = receiver_message(MessageKind::Unknown {
+                    type_name: type_name.clone(),
+                    payload: json::JsonValue::new_object(),
+                });
+
+                transport::write_packet(write, &message.as_raw_bytes()?)?;
+                Ok(Outcome::Continue)
+            },
+            Fault::MalformedStatus => {
+                let mut payload = json::JsonValue::new_object();
+                payload["type"] = "RECEIVER_STATUS".into();
+                payload["status"] = json::JsonValue::new_object();
+                payload["status"]["applications"] = json::JsonValue::new_array();
+
+                // Custom payloads are written as they are, even on builtin namespaces.
+                let message = receiver_message(MessageKind::Custom { payload: Payload::Utf8(payload) });
+                transport::write_packet(write, &message.as_raw_bytes()?)?;
+                Ok(Outcome::Continue)
+            },
+        }
+    }
+}
+
+impl Faults
+{
+    pub fn new() -> Self { Faults::default() }
+
+    /// Adds a fault.
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().unwrap().push(fault);
+    }
+
+    /// Removes all faults.
+    pub fn clear(&self) {
+        self.faults.lock().unwrap().clear();
+    }
+
+    fn contains(&self, fault: &Fault) -> bool {
+        self.faults.lock().unwrap().contains(fault)
+    }
+}
+
+impl Interceptor for Faults
+{
+    /// Performs the faults that only happen once, removing them.
+    fn before_read(&self, stream: &mut Write) -> Result<Outcome, Error> {
+        let pending: Vec<Fault> = {
+            let mut faults = self.faults.lock().unwrap();
+            let pending = faults.iter().filter(|fault| !fault.is_persistent()).cloned().collect();
+            faults.retain(Fault::is_persistent);
+            pending
+        };
+
+        for fault in pending {
+            if fault.perform(stream)? == Outcome::Close { return Ok(Outcome::Close) };
+        }
+
+        Ok(Outcome::Continue)
+    }
+
+    fn accepts(&self, message: &Message) -> bool {
+        !(message.kind == MessageKind::Ping && self.contains(&Fault::IgnorePings))
+    }
+
+    fn before_response(&self, _: &Message) {
+        let delays: Vec<Duration> = self.faults.lock().unwrap().iter().filter_map(|fault| match *fault {
+            Fault::DelayResponses(delay) => Some(delay),
+            _ => None,
+        }).collect();
+
+        for delay in delays {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Builds a message from the receiver to every sender.
+fn receiver_message(kind: MessageKind) -> Message {
+    Message {
+        source: EndpointName("receiver-0".to_owned()),
+        destination: EndpointName::broadcast(),
+        namespace: ::back::protocol::namespace::receiver(),
+        kind: kind,
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use back::net::transport;
+    use back::protocol::Message;
+    use receiver::{Interceptor, Outcome};
+    use ErrorKind;
+
+    use std::io;
+
+    fn perform(fault: Fault) -> (Outcome, Vec<u8>) {
+        let mut data = Vec::new();
+        let outcome = fault.perform(&mut data).unwrap();
+        (outcome, data)
+    }
+
+    #[test]
+    fn malformed_status_names_the_missing_field() {
+        let (_, data) = perform(Fault::MalformedStatus);
+        let packet = transport::read_packet(&mut io::Cursor::new(data)).unwrap().unwrap();
+
+        match Message::from_raw_bytes(&packet).unwrap_err().kind() {
+            &::back::protocol::ErrorKind::MissingField(ref path) => assert_eq!(path, "status.volume.controlType"),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unknown_types_are_decoded_as_unknown() {
+        let (_, data) = perform(Fault::UnknownMessageType("SELF_DESTRUCT".to_owned()));
+        let packet = transport::read_packet(&mut io::Cursor::new(data)).unwrap().unwrap();
+
+        match Message::from_raw_bytes(&packet).unwrap().kind {
+            MessageKind::Unknown { ref type_name, .. } => assert_eq!(type_name, "SELF_DESTRUCT"),
+            kind => panic!("unexpected message: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        let (_, data) = perform(Fault::OversizedFrame);
+
+        match transport::read_packet(&mut io::Cursor::new(data)).unwrap_err().kind() {
+            &ErrorKind::PacketTooLarge(..) => (),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_noticed() {
+        let (outcome, data) = perform(Fault::TruncatedFrame);
+        assert_eq!(outcome, Outcome::Close);
+
+        let mut reader = transport::Reader::new();
+        let end_of_stream = reader.read(&mut io::Cursor::new(data), &mut Vec::new()).unwrap();
+
+        assert!(end_of_stream);
+        match reader.finish().unwrap_err().kind() {
+            &ErrorKind::TruncatedPacket(..) => (),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn one_shot_faults_only_happen_once() {
+        let faults = Faults::new();
+        faults.inject(Fault::IgnorePings);
+        faults.inject(Fault::CloseMidFrame);
+
+        assert_eq!(faults.before_read(&mut Vec::new()).unwrap(), Outcome::Close);
+        assert_eq!(faults.before_read(&mut Vec::new()).unwrap(), Outcome::Continue);
+        assert!(faults.contains(&Fault::IgnorePings));
+    }
+
+    #[test]
+    fn closing_mid_frame_cuts_off_the_size() {
+        let (outcome, data) = perform(Fault::CloseMidFrame);
+        assert_eq!(outcome, Outcome::Close);
+
+        let mut reader = transport::Reader::new();
+        reader.read(&mut io::Cursor::new(data), &mut Vec::new()).unwrap();
+        assert!(reader.finish().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Sessions recorded from real receivers, replayed by the emulator.
+//!
+//! A fixture is a file of JSON lines. Each line is an object with a
+//! `direction` of `request` or `response`, and a `message` in the form
+//! written by `Message::to_json`.
+//!
+//! ```text
+//! {"direction":"request","message":{"sourceId":"sender-0","destinationId":"receiver-0","namespace":"urn:x-cast:com.google.cast.receiver","payloadUtf8":{"type":"GET_STATUS","requestId":1}}}
+//! {"direction":"response","message":{"sourceId":"receiver-0","destinationId":"sender-0","namespace":"urn:x-cast:com.google.cast.receiver","payloadUtf8":{"type":"RECEIVER_STATUS","requestId":1,"status":{...}}}}
+//! ```
+//!
+//! Each request is answered with the responses recorded after it, so a
+//! fixture must start with a request. Blank lines are ignored.
+
+pub use recording::Direction;
+
+use {Error, ErrorKind};
+use back::protocol::{Message, MessageKind, Payload};
+
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use json;
+
+/// A recorded message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry
+{
+    /// The line of the fixture the message was read from.
+    pub line: usize,
+    pub direction: Direction,
+    pub message: Message,
+}
+
+/// A recorded session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixture
+{
+    pub entries: Vec<Entry>,
+}
+
+/// A message that was not what the fixture expected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch
+{
+    /// The line of the request that was expected, if the fixture had not run out.
+    pub line: Option<usize>,
+    /// The request that was expected.
+    pub expected: Option<Message>,
+    /// The message that was actually received.
+    pub received: Message,
+}
+
+/// Plays a fixture back, one request at a time.
+#[derive(Clone, Debug)]
+pub struct Replay
+{
+    fixture: Fixture,
+    /// The index of the next entry to play.
+    position: usize,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Fixture
+{
+    /// Reads a fixture from a file.
+    pub fn open<P>(path: P) -> Result<Self, Error> where P: AsRef<Path> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+
+        Fixture::parse(&text)
+    }
+
+    /// Reads a fixture from JSON lines.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() { continue };
+
+            let line_number = index + 1;
+            let invalid = |reason: String| ErrorKind::InvalidFixture(line_number, reason);
+
+            let data = json::parse(line).map_err(|e| invalid(e.to_string()))?;
+            let direction = data["direction"].as_str().and_then(Direction::from_name)
+                .ok_or_else(|| invalid("expected a direction of 'request' or 'response'".to_owned()))?;
+            let message = Message::from_json(&data["message"]).map_err(|e| invalid(e.to_string()))?;
+
+            if entries.is_empty() && direction == Direction::Response {
+                return Err(invalid("a fixture must start with a request".to_owned()).into());
+            }
+
+            entries.push(Entry {
+                line: line_number,
+                direction: direction,
+                message: message,
+            });
+        }
+
+        Ok(Fixture { entries: entries })
+    }
+
+    /// Writes the fixture as JSON lines.
+    pub fn to_json_lines(&self) -> String {
+        self.entries.iter().map(|entry| {
+            let mut data = json::JsonValue::new_object();
+            data["direction"] = entry.direction.as_str().into();
+            data["message"] = entry.message.to_json();
+
+            json::stringify(data) + "\n"
+        }).collect()
+    }
+}
+
+impl Replay
+{
+    pub fn new(fixture: Fixture) -> Self {
+        Replay {
+            fixture: fixture,
+            position: 0,
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// Handles a message from a sender.
+    ///
+    /// Returns the recorded responses if the message was the next request,
+    /// with their destination changed to the sender that asked. Returns
+    /// `None` for connection and heartbeat messages that the fixture did
+    /// not expect, so that they can be answered as usual.
+    pub fn handle(&mut self, message: &Message) -> Option<Vec<Message>> {
+        let expected = self.fixture.entries.get(self.position).cloned();
+
+        match expected {
+            Some(ref entry) if entry.direction == Direction::Request && matches(&entry.message, message) => {
+                self.position += 1;
+                Some(self.take_responses(&entry.message, message))
+            },
+            _ if is_housekeeping(message) => None,
+            _ => {
+                let expected = self.fixture.entries[self.position..].iter()
+                    .find(|entry| entry.direction == Direction::Request);
+
+                self.mismatches.push(Mismatch {
+                    line: expected.map(|entry| entry.line),
+                    expected: expected.map(|entry| entry.message.clone()),
+                    received: message.clone(),
+                });
+                Some(Vec::new())
+            },
+        }
+    }
+
+    /// Checks whether every recorded message has been played.
+    pub fn is_finished(&self) -> bool {
+        self.position == self.fixture.entries.len()
+    }
+
+    /// Gets the messages that were not what the fixture expected.
+    pub fn mismatches(&self) -> &[Mismatch] { &self.mismatches }
+
+    /// Takes the responses that follow the request that was just played.
+    ///
+    /// Responses are addressed to the sender that asked, with the request
+    /// ID that it chose.
+    fn take_responses(&mut self, recorded: &Message, received: &Message) -> Vec<Message> {
+        let recorded_id = request_id(recorded);
+        let received_id = request_id(received);
+        let mut responses = Vec::new();
+
+        while let Some(entry) = self.fixture.entries.get(self.position) {
+            if entry.direction != Direction::Response { break };
+
+            let mut response = entry.message.clone();
+            if !response.destination.is_broadcast() {
+                response.destination = received.source.clone();
+            }
+
+            match (recorded_id.clone(), received_id.clone()) {
+                (Some(recorded_id), Some(received_id)) if request_id(&response).as_ref() == Some(&recorded_id) => {
+                    response = with_request_id(response, received_id);
+                },
+                _ => (),
+            }
+
+            responses.push(response);
+            self.position += 1;
+        }
+
+        responses
+    }
+}
+
+impl fmt::Display for Mismatch
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match (self.line, self.expected.as_ref()) {
+            (Some(line), Some(expected)) => {
+                write!(fmt, "expected the request on line {}: {}, but received: {}",
+                       line, json::stringify(expected.to_json()), json::stringify(self.received.to_json()))
+            },
+            _ => write!(fmt, "expected no more requests, but received: {}", json::stringify(self.received.to_json())),
+        }
+    }
+}
+
+/// Checks whether a message is the request that was recorded.
+///
+/// The source and the request ID are not compared, because they are
+/// chosen by the sender.
+fn matches(recorded: &Message, received: &Message) -> bool {
+    recorded.destination == received.destination &&
+        recorded.namespace == received.namespace &&
+        without_request_id(recorded.kind.to_payload()) == without_request_id(received.kind.to_payload())
+}
+
+/// Removes the request ID from a payload.
+fn without_request_id(payload: Payload) -> Payload {
+    match payload {
+        Payload::Utf8(mut data) => {
+            data.remove("requestId");
+            Payload::Utf8(data)
+        },
+        payload => payload,
+    }
+}
+
+/// Gets the request ID of a message, if it has one.
+fn request_id(message: &Message) -> Option<json::JsonValue> {
+    match message.kind.to_payload() {
+        Payload::Utf8(ref data) if data.has_key("requestId") => Some(data["requestId"].clone()),
+        _ => None,
+    }
+}
+
+/// Changes the request ID of a message.
+fn with_request_id(message: Message, request_id: json::JsonValue) -> Message {
+    let mut data = match message.kind.to_payload() {
+        Payload::Utf8(data) => data,
+        Payload::Binary(..) => return message,
+    };
+    data["requestId"] = request_id;
+
+    match MessageKind::from_json(&message.namespace, &data) {
+        Ok(kind) => Message { kind: kind, ..message },
+        // The message was recorded from a receiver, so it can be decoded.
+        Err(..) => message,
+    }
+}
+
+/// Checks whether a message only keeps the connection alive.
+fn is_housekeeping(message: &Message) -> bool {
+    message.namespace == ::back::protocol::namespace::connection() ||
+        message.namespace == ::back::protocol::namespace::heartbeat()
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use back::protocol::{Message, MessageKind, EndpointName};
+
+    fn receiver_message(source: &str, destination: &str, kind: MessageKind) -> Message {
+        Message {
+            source: EndpointName(source.to_owned()),
+            destination: EndpointName(destination.to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: kind,
+        }
+    }
+
+    fn fixture() -> Fixture {
+        Fixture {
+            entries: vec![
+                Entry {
+                    line: 1,
+                    direction: Direction::Request,
+                    message: receiver_message("sender-0", "receiver-0", MessageKind::GetDeviceInfo { request_id: 1 }),
+                },
+                Entry {
+                    line: 2,
+                    direction: Direction::Response,
+                    message: receiver_message("receiver-0", "sender-0", MessageKind::InvalidRequest {
+                        reason: ::back::protocol::InvalidRequestReason::InvalidCommand,
+                        request_id: 1,
+                    }),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn fixtures_survive_a_round_trip() {
+        assert_eq!(Fixture::parse(&fixture().to_json_lines()).unwrap(), fixture());
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        let text = fixture().to_json_lines() + "\n{ \"direction\": \"sideways\" }\n";
+
+        match Fixture::parse(&text).unwrap_err().kind() {
+            &ErrorKind::InvalidFixture(line, _) => assert_eq!(line, 4),
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn responses_go_to_the_sender_that_asked() {
+        let mut replay = Replay::new(fixture());
+        let responses = replay.handle(&receiver_message("sender-7", "receiver-0", MessageKind::GetDeviceInfo { request_id: 1 })).unwrap();
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].destination, EndpointName("sender-7".to_owned()));
+        assert!(replay.is_finished());
+        assert!(replay.mismatches().is_empty());
+    }
+
+    #[test]
+    fn request_ids_are_those_of_the_sender() {
+        let mut replay = Replay::new(fixture());
+        let responses = replay.handle(&receiver_message("sender-0", "receiver-0", MessageKind::GetDeviceInfo { request_id: 7 })).unwrap();
+
+        assert_eq!(responses, vec![receiver_message("receiver-0", "sender-0", MessageKind::InvalidRequest {
+            reason: ::back::protocol::InvalidRequestReason::InvalidCommand,
+            request_id: 7,
+        })]);
+        assert!(replay.mismatches().is_empty());
+    }
+
+    #[test]
+    fn unexpected_requests_are_mismatches() {
+        let mut replay = Replay::new(fixture());
+        let request = receiver_message("sender-0", "receiver-0", MessageKind::GetStatus { request_id: 1 });
+
+        assert_eq!(replay.handle(&request), Some(Vec::new()));
+        assert_eq!(replay.mismatches(), &[Mismatch {
+            line: Some(1),
+            expected: Some(fixture().entries[0].message.clone()),
+            received: request,
+        }]);
+        assert!(!replay.is_finished());
+    }
+
+    #[test]
+    fn heartbeats_are_left_alone() {
+        let mut replay = Replay::new(fixture());
+        let ping = Message {
+            namespace: ::back::protocol::namespace::heartbeat(),
+            ..receiver_message("sender-0", "receiver-0", MessageKind::Ping)
+        };
+
+        assert_eq!(replay.handle(&ping), None);
+        assert!(replay.mismatches().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! An emulated Default Media Receiver.
+
+use back::protocol::{MessageKind, MediaStatus, MediaVolume, MediaSessionId, PlayerState,
+                     SeekPosition, ResumeState, RequestId, InvalidRequestReason};
+use receiver::Response;
+use VolumeLevel;
+
+use json;
+
+/// The idle reason given when media has played to the end.
+const FINISHED: &'static str = "FINISHED";
+/// The idle reason given when a sender stopped the media.
+const CANCELLED: &'static str = "CANCELLED";
+
+/// The commands the emulated player supports: pause, seek, stream
+/// volume and stream mute.
+const SUPPORTED_MEDIA_COMMANDS: u64 = 1 | 2 | 4 | 8;
+
+/// A media player whose timeline only moves when it is told to.
+///
+/// Time is advanced with `MediaPlayer::advance`, which makes playback
+/// deterministic in tests.
+#[derive(Clone, Debug)]
+pub struct MediaPlayer
+{
+    /// The media that is loaded, if any.
+    session: Option<LoadedMedia>,
+    /// An accumulator used to give us unique media session IDs.
+    media_session_id_accumulator: MediaSessionId,
+}
+
+#[derive(Clone, Debug)]
+struct LoadedMedia
+{
+    media_session_id: MediaSessionId,
+    media: json::JsonValue,
+    player_state: PlayerState,
+    idle_reason: Option<String>,
+    current_time: f64,
+    playback_rate: f32,
+    volume: MediaVolume,
+}
+
+impl MediaPlayer
+{
+    pub fn new() -> Self {
+        MediaPlayer {
+            session: None,
+            media_session_id_accumulator: 1,
+        }
+    }
+
+    /// Handles a message on the media namespace.
+    ///
+    /// Changes to the media are broadcast to every sender.
+    pub fn handle(&mut self, kind: &MessageKind) -> Option<Response> {
+        let (request_id, result) = match *kind {
+            MessageKind::MediaGetStatus { request_id, .. } => return Some(Response::Reply(self.status_message(request_id))),
+            MessageKind::MediaLoad { ref media, autoplay, current_time, request_id } => {
+                self.load(media.clone(), autoplay.unwrap_or(true), current_time.unwrap_or(0.0));
+                (request_id, Ok(()))
+            },
+            MessageKind::MediaPlay { media_session_id, request_id } => {
+                (request_id, self.update(media_session_id, |media| media.player_state = PlayerState::Playing))
+            },
+            MessageKind::MediaPause { media_session_id, request_id } => {
+                (request_id, self.update(media_session_id, |media| media.player_state = PlayerState::Paused))
+            },
+            MessageKind::MediaStop { media_session_id, request_id } => {
+                (request_id, self.update(media_session_id, |media| {
+                    media.player_state = PlayerState::Idle;
+                    media.idle_reason = Some(CANCELLED.to_owned());
+                }))
+            },
+            MessageKind::MediaSeek { media_session_id, position, resume_state, request_id } => {
+                (request_id, self.update(media_session_id, |media| {
+                    let time = match position {
+                        SeekPosition::Absolute(time) => time,
+                        SeekPosition::Relative(offset) => media.current_time + offset,
+                    };
+                    media.current_time = media.clamp_time(time);
+
+                    match resume_state {
+                        Some(ResumeState::PlaybackStart) => media.player_state = PlayerState::Playing,
+                        Some(ResumeState::PlaybackPause) => media.player_state = PlayerState::Paused,
+                        None => (),
+                    }
+                }))
+            },
+            MessageKind::MediaSetPlaybackRate { media_session_id, playback_rate, request_id } => {
+                (request_id, self.update(media_session_id, |media| media.playback_rate = playback_rate))
+            },
+            MessageKind::MediaSetVolume { media_session_id, level, muted, request_id } => {
+                (request_id, self.update(media_session_id, |media| {
+                    if let Some(level) = level { media.volume.level = Some(level.clamp()) };
+                    if let Some(muted) = muted { media.volume.muted = Some(muted) };
+                }))
+            },
+            _ => return None,
+        };
+
+        match result {
+            Ok(()) => Some(Response::Broadcast(self.status_message(request_id))),
+            Err(reason) => Some(Response::Reply(MessageKind::InvalidRequest { reason: reason, request_id: request_id })),
+        }
+    }
+
+    /// Moves the timeline forward.
+    ///
+    /// Returns the new status if playback finished, which should be
+    /// broadcast to every sender.
+    pub fn advance(&mut self, seconds: f64) -> Option<MessageKind> {
+        let finished = match self.session {
+            Some(ref mut media) if media.player_state == PlayerState::Playing => {
+                let time = media.current_time + seconds * media.playback_rate as f64;
+                media.current_time = media.clamp_time(time);
+
+                if media.duration().map_or(false, |duration| media.current_time >= duration) {
+                    media.player_state = PlayerState::Idle;
+                    media.idle_reason = Some(FINISHED.to_owned());
+                    true
+                } else {
+                    false
+                }
+            },
+            _ => false,
+        };
+
+        if finished { Some(self.status_message(0)) } else { None }
+    }
+
+    /// Gets the status of the loaded media.
+    pub fn status(&self) -> Vec<MediaStatus> {
+        self.session.iter().map(LoadedMedia::status).collect()
+    }
+
+    fn load(&mut self, media: json::JsonValue, autoplay: bool, current_time: f64) {
+        let media_session_id = self.media_session_id_accumulator;
+        self.media_session_id_accumulator += 1;
+
+        let mut loaded = LoadedMedia {
+            media_session_id: media_session_id,
+            media: media,
+            player_state: if autoplay { PlayerState::Playing } else { PlayerState::Paused },
+            idle_reason: None,
+            current_time: 0.0,
+            playback_rate: 1.0,
+            volume: MediaVolume { level: Some(VolumeLevel::max()), muted: Some(false) },
+        };
+        loaded.current_time = loaded.clamp_time(current_time);
+
+        self.session = Some(loaded);
+    }
+
+    /// Changes the loaded media, if it is the session the sender asked for.
+    fn update<F>(&mut self, media_session_id: MediaSessionId, f: F) -> Result<(), InvalidRequestReason>
+        where F: FnOnce(&mut LoadedMedia) {
+        match self.session {
+            Some(ref mut media) if media.media_session_id == media_session_id => {
+                f(media);
+                Ok(())
+            },
+            _ => Err(InvalidRequestReason::InvalidMediaSessionId),
+        }
+    }
+
+    fn status_message(&self, request_id: RequestId) -> MessageKind {
+        MessageKind::MediaStatus {
+            statuses: self.status(),
+            request_id: request_id,
+        }
+    }
+}
+
+impl LoadedMedia
+{
+    fn duration(&self) -> Option<f64> {
+        self.media["duration"].as_f64()
+    }
+
+    fn clamp_time(&self, time: f64) -> f64 {
+        let time = time.max(0.0);
+
+        match self.duration() {
+            Some(duration) => time.min(duration),
+            None => time,
+        }
+    }
+
+    fn status(&self) -> MediaStatus {
+        MediaStatus {
+            media_session_id: self.media_session_id,
+            player_state: self.player_state.clone(),
+            idle_reason: self.idle_reason.clone(),
+            current_time: self.current_time,
+            playback_rate: self.playback_rate,
+            volume: self.volume,
+            supported_media_commands: SUPPORTED_MEDIA_COMMANDS,
+            media: Some(self.media.clone()),
+            live_seekable_range: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use back::protocol::{MessageKind, PlayerState, SeekPosition, ResumeState, InvalidRequestReason};
+    use receiver::Response;
+
+    use json;
+
+    fn song(duration: f64) -> json::JsonValue {
+        let mut media = json::JsonValue::new_object();
+        media["contentId"] = "song.mp3".into();
+        media["duration"] = duration.into();
+        media
+    }
+
+    fn loaded_player(duration: f64) -> MediaPlayer {
+        let mut player = MediaPlayer::new();
+        player.handle(&MessageKind::MediaLoad { media: song(duration), autoplay: None, current_time: None, request_id: 1 });
+        player
+    }
+
+    #[test]
+    fn playback_follows_the_virtual_clock() {
+        let mut player = loaded_player(60.0);
+
+        assert_eq!(player.advance(10.0), None);
+        assert_eq!(player.status()[0].current_time, 10.0);
+        assert_eq!(player.status()[0].player_state, PlayerState::Playing);
+    }
+
+    #[test]
+    fn paused_media_does_not_move() {
+        let mut player = loaded_player(60.0);
+        player.handle(&MessageKind::MediaPause { media_session_id: 1, request_id: 2 });
+        player.advance(10.0);
+
+        assert_eq!(player.status()[0].current_time, 0.0);
+        assert_eq!(player.status()[0].player_state, PlayerState::Paused);
+    }
+
+    #[test]
+    fn finishes_at_the_end_of_the_media() {
+        let mut player = loaded_player(60.0);
+        player.advance(50.0);
+
+        match player.advance(20.0) {
+            Some(MessageKind::MediaStatus { ref statuses, .. }) => {
+                assert_eq!(statuses[0].current_time, 60.0);
+                assert_eq!(statuses[0].player_state, PlayerState::Idle);
+                assert_eq!(statuses[0].idle_reason, Some("FINISHED".to_owned()));
+            },
+            other => panic!("expected a finished status, got {:?}", other),
+        }
+
+        assert_eq!(player.advance(10.0), None);
+    }
+
+    #[test]
+    fn seeks_relative_to_the_current_time() {
+        let mut player = loaded_player(60.0);
+        player.advance(40.0);
+
+        player.handle(&MessageKind::MediaSeek {
+            media_session_id: 1,
+            position: SeekPosition::Relative(-30.0),
+            resume_state: Some(ResumeState::PlaybackPause),
+            request_id: 2,
+        });
+
+        assert_eq!(player.status()[0].current_time, 10.0);
+        assert_eq!(player.status()[0].player_state, PlayerState::Paused);
+    }
+
+    #[test]
+    fn rejects_unknown_media_sessions() {
+        let mut player = loaded_player(60.0);
+
+        assert_eq!(player.handle(&MessageKind::MediaPlay { media_session_id: 7, request_id: 2 }),
+                   Some(Response::Reply(MessageKind::InvalidRequest {
+                       reason: InvalidRequestReason::InvalidMediaSessionId,
+                       request_id: 2,
+                   })));
+    }
+
+    #[test]
+    fn playback_rate_speeds_up_the_clock() {
+        let mut player = loaded_player(60.0);
+        player.handle(&MessageKind::MediaSetPlaybackRate { media_session_id: 1, playback_rate: 2.0, request_id: 2 });
+        player.advance(10.0);
+
+        assert_eq!(player.status()[0].current_time, 20.0);
+    }
+}
@@ -0,0 +1,530 @@
+//! A local Cast receiver for testing senders without a real device.
+//!
+//! The emulator is a `receiver::ReceiverHandler` served by a
+//! `receiver::Server`, with some extra features for tests.
+//!
+//! The emulator listens for TLS connections on the loopback interface and
+//! speaks enough of the CASTV2 protocol for `Device::connect` to work
+//! against it: virtual connections, heartbeats, receiver status,
+//! launching and stopping applications, and volume.
+//!
+//! Launching the Default Media Receiver gives a media player that accepts
+//! `LOAD`, `PLAY`, `PAUSE`, `SEEK` and `STOP`. Its timeline runs on a
+//! virtual clock that only moves when `Emulator::advance` is called, so
+//! tests of playback progress are deterministic.
+//!
+//! Sessions recorded from real receivers can be replayed by passing a
+//! `Fixture` to `Options::fixture`. The emulator then answers with the
+//! recorded responses, and `Emulator::mismatches` reports the requests
+//! that differed from the recording.
+//!
+//! With `Options::advertise`, the emulator answers mDNS queries for Cast
+//! devices, so that it can be found by `discovery::run`.
+//!
+//! Misbehaving receivers can be scripted with `Emulator::inject`, which
+//! takes a `Fault` such as a dropped connection or a malformed message.
+//!
+//! ```ignore
+//! let emulator = Emulator::start(emulator::Options::new().name("Living Room"))?;
+//!
+//! let mut io = gcast::back::net::Io::new()?;
+//! let mut device = Device::connect(emulator.device_info(), &mut io)?;
+//! ```
+
+pub use self::state::State;
+pub use self::media_player::MediaPlayer;
+pub use self::fault::{Fault, Faults};
+pub use self::fixture::{Fixture, Mismatch};
+pub use self::advertiser::Advertiser;
+
+pub mod state;
+pub mod media_player;
+pub mod fault;
+pub mod fixture;
+pub mod advertiser;
+
+use {ApplicationId, Namespace, DeviceInfo, Error, Status, Volume, VolumeLevel};
+use receiver::{Server, ReceiverHandler};
+use apps;
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use uuid::Uuid;
+
+/// Options for starting an emulator.
+///
+/// ```
+/// # use gcast::emulator::Options;
+/// let options = Options::new()
+///     .port(0)
+///     .name("Kitchen");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Options
+{
+    /// The port to listen on, or zero to pick any free port.
+    pub port: u16,
+    /// The name the receiver gives itself.
+    pub name: String,
+    /// The unique identifier of the receiver.
+    pub uuid: Uuid,
+    /// The applications that can be launched.
+    pub apps: Vec<EmulatedApp>,
+    /// The volume of the receiver when it starts.
+    pub volume: Volume,
+    /// A recorded session to replay instead of emulating a receiver.
+    pub fixture: Option<Fixture>,
+    /// Whether to answer mDNS queries on the loopback interface.
+    pub advertise: bool,
+}
+
+/// An application that can be launched on the emulator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmulatedApp
+{
+    /// The identifier used to launch the application.
+    pub app_id: ApplicationId,
+    /// The name shown in the receiver status.
+    pub display_name: String,
+    /// The namespaces the application communicates over.
+    pub namespaces: Vec<Namespace>,
+}
+
+/// A running emulator.
+///
+/// The emulator stops when it is dropped.
+pub struct Emulator
+{
+    uuid: Uuid,
+    name: String,
+    state: Arc<Mutex<State>>,
+    faults: Arc<Faults>,
+    server: Server,
+    advertiser: Option<Advertiser>,
+}
+
+impl Options
+{
+    /// Creates options for a receiver that can launch a handful of common applications.
+    pub fn new() -> Self {
+        Options {
+            port: 0,
+            name: "gcast emulator".to_owned(),
+            uuid: Uuid::nil(),
+            apps: vec![
+                EmulatedApp::new(apps::default_media_receiver(), "Default Media Receiver",
+                                 vec![::back::protocol::namespace::media()]),
+                EmulatedApp::new(apps::backdrop(), "Backdrop", Vec::new()),
+                EmulatedApp::new(apps::youtube(), "YouTube", Vec::new()),
+            ],
+            volume: Volume {
+                control_type: "attenuation".to_owned(),
+                level: VolumeLevel(0.5),
+                muted: false,
+                step_interval: VolumeLevel(0.05),
+            },
+            fixture: None,
+            advertise: false,
+        }
+    }
+
+    /// Sets the port to listen on.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the name the receiver gives itself.
+    pub fn name<S>(mut self, name: S) -> Self where S: Into<String> {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the unique identifier of the receiver.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Adds an application that can be launched.
+    pub fn app(mut self, app: EmulatedApp) -> Self {
+        self.apps.push(app);
+        self
+    }
+
+    /// Sets the volume of the receiver when it starts.
+    pub fn volume(mut self, volume: Volume) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Replays a recorded session.
+    ///
+    /// Connection and heartbeat messages that are not part of the
+    /// recording are still answered by the emulator.
+    pub fn fixture(mut self, fixture: Fixture) -> Self {
+        self.fixture = Some(fixture);
+        self
+    }
+
+    /// Sets whether the emulator can be found with `discovery::run`.
+    pub fn advertise(mut self, advertise: bool) -> Self {
+        self.advertise = advertise;
+        self
+    }
+}
+
+impl Emulator
+{
+    /// Starts listening for senders on a background thread.
+    pub fn start(options: Options) -> Result<Self, Error> {
+        let listener = TcpListener::bind((Ipv4Addr::new(127, 0, 0, 1), options.port))?;
+
+        let state = Arc::new(Mutex::new(State::new(&options)));
+        let faults = Arc::new(Faults::new());
+        let server = Server::start_intercepted(listener, state.clone(), faults.clone())?;
+
+        let mut emulator = Emulator {
+            uuid: options.uuid,
+            name: options.name,
+            state: state,
+            faults: faults,
+            server: server,
+            advertiser: None,
+        };
+
+        if options.advertise {
+            emulator.advertiser = Some(Advertiser::start(&emulator.device_info(), Ipv4Addr::new(127, 0, 0, 1))?);
+        }
+
+        Ok(emulator)
+    }
+
+    /// Gets the address the emulator is listening on.
+    pub fn address(&self) -> SocketAddr { self.server.address() }
+
+    /// Gets the information needed to connect to the emulator.
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            ip_addr: Ipv4Addr::new(127, 0, 0, 1),
+            port: self.address().port(),
+            uuid: self.uuid,
+            name: Some(self.name.clone()),
+        }
+    }
+
+    /// Gets the current status of the emulated receiver.
+    pub fn status(&self) -> Status {
+        self.state.lock().unwrap().status()
+    }
+
+    /// Moves the virtual clock forward.
+    ///
+    /// If media finishes playing as a result, its status is broadcast
+    /// to every connected sender.
+    pub fn advance(&self, duration: Duration) {
+        let seconds = duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0;
+        let messages = self.state.lock().unwrap().advance(seconds);
+
+        for message in messages {
+            self.server.broadcast(message);
+        }
+    }
+
+    /// Gets the requests that were not what the fixture expected.
+    pub fn mismatches(&self) -> Vec<Mismatch> {
+        let state = self.state.lock().unwrap();
+        state.replay.as_ref().map_or(Vec::new(), |replay| replay.mismatches().to_vec())
+    }
+
+    /// Checks whether every message in the fixture has been played.
+    ///
+    /// This is always true when no fixture is being replayed.
+    pub fn is_fixture_finished(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.replay.as_ref().map_or(true, |replay| replay.is_finished())
+    }
+
+    /// Makes something go wrong.
+    ///
+    /// ```ignore
+    /// emulator.inject(Fault::IgnorePings);
+    /// emulator.inject(Fault::TruncatedFrame);
+    /// ```
+    pub fn inject(&self, fault: Fault) {
+        self.faults.inject(fault);
+    }
+
+    /// Removes all faults, so the receiver behaves again.
+    pub fn clear_faults(&self) {
+        self.faults.clear();
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use back::net::transport;
+    use back::net::Io;
+    use back::protocol::{Message, MessageKind, EndpointName, InvalidRequestReason};
+    use {apps, Device, Event, ErrorKind, RequestError, SessionId};
+
+    use std::net::TcpStream;
+    use std::time::Instant;
+
+    use openssl::ssl;
+
+    fn connect(emulator: &Emulator) -> ssl::SslStream<TcpStream> {
+        let mut connector = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls()).unwrap();
+        connector.set_verify(ssl::SSL_VERIFY_NONE);
+        let connector = connector.build();
+
+        let stream = TcpStream::connect(emulator.address()).unwrap();
+        connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream).unwrap()
+    }
+
+    /// Drives a device until `done` is satisfied, returning the events raised on the way.
+    fn poll_until<F>(io: &mut Io, device: &mut Device, mut done: F) -> Vec<Event>
+        where F: FnMut(&Device, &[Event]) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut events = Vec::new();
+
+        while !done(device, &events) {
+            assert!(Instant::now() < deadline, "gave up waiting for the device");
+
+            io.poll.poll(&mut io.events, Some(Duration::from_millis(20))).unwrap();
+            for event in io.events.iter() {
+                device.handle_io(event).unwrap();
+            }
+            device.update().unwrap();
+            events.extend(device.events());
+        }
+
+        events
+    }
+
+    /// Drives a device until it fails, returning the error.
+    fn poll_until_error(io: &mut Io, device: &mut Device) -> Error {
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        loop {
+            assert!(Instant::now() < deadline, "the device never failed");
+
+            io.poll.poll(&mut io.events, Some(Duration::from_millis(20))).unwrap();
+            for event in io.events.iter() {
+                if let Err(e) = device.handle_io(event) { return e };
+            }
+            if let Err(e) = device.update() { return e };
+        }
+    }
+
+    /// Connects a device to an emulator that has a fault, returning the error it fails with.
+    fn error_with_fault(fault: Fault) -> Error {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        emulator.inject(fault);
+
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect(emulator.device_info(), &mut io).unwrap();
+        device.update_status().unwrap();
+
+        poll_until_error(&mut io, &mut device)
+    }
+
+    fn get_status() -> Message {
+        Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: MessageKind::GetStatus { request_id: 1 },
+        }
+    }
+
+    #[test]
+    fn devices_connect_and_launch_apps() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect(emulator.device_info(), &mut io).unwrap();
+        device.update_status().unwrap();
+
+        let events = poll_until(&mut io, &mut device, |device, _| device.status().is_some());
+        assert!(events.iter().any(|event| match *event { Event::StatusUpdated => true, _ => false }));
+        assert_eq!(device.status(), Some(&emulator.status()));
+
+        device.launch(apps::default_media_receiver()).unwrap();
+        poll_until(&mut io, &mut device, |device, _| {
+            device.status().map_or(false, |status| !status.applications.is_empty())
+        });
+
+        let status = device.status().unwrap();
+        assert_eq!(status.applications[0].id, apps::default_media_receiver());
+        assert_eq!(status, &emulator.status());
+    }
+
+    #[test]
+    fn advertised_emulators_are_discovered() {
+        let uuid = Uuid::parse_str("5d8c2c8e-3f1b-4a7e-9c55-0a6e4c2b9f10").unwrap();
+        let emulator = Emulator::start(Options::new().name("Den").uuid(uuid).advertise(true)).unwrap();
+
+        let mut found = Vec::new();
+        ::discovery::run(Duration::from_secs(2), |info| found.push(info)).unwrap();
+
+        // Real devices on the network may answer too.
+        found.retain(|info| info.uuid == uuid);
+        assert_eq!(found, vec![emulator.device_info()]);
+        assert_eq!(found[0].name, Some("Den".to_owned()));
+    }
+
+    #[test]
+    fn answers_over_tls() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        let mut stream = connect(&emulator);
+
+        let request = get_status();
+        transport::write_packet(&mut stream, &request.as_raw_bytes().unwrap()).unwrap();
+
+        let response = transport::read_packet(&mut stream).unwrap().unwrap();
+        let response = Message::from_raw_bytes(&response).unwrap();
+
+        assert_eq!(response.kind, MessageKind::ReceiverStatus { status: emulator.status(), request_id: 1 });
+    }
+
+    #[test]
+    fn replays_fixtures() {
+        let request = get_status();
+        let response = Message {
+            source: EndpointName("receiver-0".to_owned()),
+            destination: EndpointName("sender-0".to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: MessageKind::Unknown { type_name: "RECORDED".to_owned(), payload: {
+                let mut payload = ::json::JsonValue::new_object();
+                payload["type"] = "RECORDED".into();
+                payload
+            }},
+        };
+
+        let fixture = Fixture {
+            entries: vec![
+                fixture::Entry { line: 1, direction: fixture::Direction::Request, message: request.clone() },
+                fixture::Entry { line: 2, direction: fixture::Direction::Response, message: response.clone() },
+            ],
+        };
+
+        let emulator = Emulator::start(Options::new().fixture(fixture)).unwrap();
+        let mut stream = connect(&emulator);
+
+        transport::write_packet(&mut stream, &request.as_raw_bytes().unwrap()).unwrap();
+        let received = transport::read_packet(&mut stream).unwrap().unwrap();
+
+        assert_eq!(Message::from_raw_bytes(&received).unwrap(), response);
+        assert!(emulator.is_fixture_finished());
+        assert!(emulator.mismatches().is_empty());
+    }
+
+    #[test]
+    fn injected_faults_reach_the_sender() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        emulator.inject(Fault::MalformedStatus);
+        let mut stream = connect(&emulator);
+
+        let packet = transport::read_packet(&mut stream).unwrap().unwrap();
+        assert!(Message::from_raw_bytes(&packet).is_err());
+    }
+
+    #[test]
+    fn stopping_unknown_sessions_is_refused() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect(emulator.device_info(), &mut io).unwrap();
+
+        // Use up a few IDs, so the answer can't match by chance.
+        for _ in 0..3 { device.update_status().unwrap(); }
+        let request_id = device.stop(SessionId(Uuid::parse_str("0cd9c5e1-7c1e-4f4a-8f3b-4f0e1e6b2a77").unwrap())).unwrap();
+
+        let events = poll_until(&mut io, &mut device, |_, events| {
+            events.iter().any(|event| match *event { Event::RequestFailed { .. } => true, _ => false })
+        });
+
+        let failures: Vec<_> = events.iter().filter_map(|event| match *event {
+            Event::RequestFailed { request_id, ref error } => Some((request_id, error.clone())),
+            _ => None,
+        }).collect();
+        assert_eq!(failures, vec![(request_id, RequestError::InvalidRequest(InvalidRequestReason::InvalidParams))]);
+    }
+
+    #[test]
+    fn devices_notice_frames_cut_off_mid_size() {
+        match *error_with_fault(Fault::CloseMidFrame).kind() {
+            ErrorKind::TruncatedPacket(4, 2) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn devices_notice_truncated_frames() {
+        match *error_with_fault(Fault::TruncatedFrame).kind() {
+            ErrorKind::TruncatedPacket(size, read) => assert_eq!(read, size / 2),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn devices_refuse_oversized_frames() {
+        match *error_with_fault(Fault::OversizedFrame).kind() {
+            ErrorKind::PacketTooLarge(size) => assert_eq!(size, transport::MAX_PACKET_SIZE + 1),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn devices_survive_malformed_statuses() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        emulator.inject(Fault::MalformedStatus);
+
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect(emulator.device_info(), &mut io).unwrap();
+
+        poll_until(&mut io, &mut device, |_, events| {
+            events.iter().any(|event| match *event { Event::InvalidMessage { .. } => true, _ => false })
+        });
+        assert!(device.status().is_none());
+
+        device.update_status().unwrap();
+        poll_until(&mut io, &mut device, |device, _| device.status().is_some());
+        assert_eq!(device.status(), Some(&emulator.status()));
+    }
+
+    #[test]
+    fn responses_can_be_delayed() {
+        let delay = Duration::from_millis(300);
+        let emulator = Emulator::start(Options::new()).unwrap();
+        emulator.inject(Fault::DelayResponses(delay));
+
+        let mut io = Io::new().unwrap();
+        let mut device = Device::connect(emulator.device_info(), &mut io).unwrap();
+
+        let started = Instant::now();
+        device.update_status().unwrap();
+        poll_until(&mut io, &mut device, |device, _| device.status().is_some());
+
+        assert!(started.elapsed() >= delay);
+    }
+
+    #[test]
+    fn pings_can_be_ignored() {
+        let emulator = Emulator::start(Options::new()).unwrap();
+        emulator.inject(Fault::IgnorePings);
+        let mut stream = connect(&emulator);
+
+        let ping = Message { kind: MessageKind::Ping, ..get_status() };
+        transport::write_packet(&mut stream, &ping.as_raw_bytes().unwrap()).unwrap();
+        transport::write_packet(&mut stream, &get_status().as_raw_bytes().unwrap()).unwrap();
+
+        // The status is the first answer, as the ping never gets one.
+        let response = transport::read_packet(&mut stream).unwrap().unwrap();
+        let response = Message::from_raw_bytes(&response).unwrap();
+        assert_eq!(response.kind, MessageKind::ReceiverStatus { status: emulator.status(), request_id: 1 });
+    }
+}
@@ -0,0 +1,309 @@
+//! The state of an emulated receiver.
+
+use {ApplicationId, AppAvailability, Namespace, EndpointName, LaunchOptions, SessionId, Status,
+     Volume, VolumeLevel};
+use back::protocol::{Message, MessageKind, DeviceDescription, InvalidRequestReason, LaunchErrorReason};
+use back::protocol::status::Application;
+use receiver::{self, ReceiverHandler, Response};
+use emulator::{Options, EmulatedApp};
+use emulator::media_player::MediaPlayer;
+use emulator::fixture::Replay;
+use apps;
+
+use uuid::Uuid;
+
+/// The state of an emulated receiver, shared between all connected senders.
+#[derive(Clone, Debug)]
+pub struct State
+{
+    /// The name the receiver gives itself.
+    pub name: String,
+    /// The unique identifier of the receiver.
+    pub uuid: Uuid,
+    /// The applications that can be launched.
+    pub apps: Vec<EmulatedApp>,
+    /// The volume of the receiver.
+    pub volume: Volume,
+    /// The application that is currently running, if any.
+    pub running_app: Option<Application>,
+    /// The media player of the Default Media Receiver, when it is running.
+    pub media_player: MediaPlayer,
+    /// The recorded session being replayed, if any.
+    pub replay: Option<Replay>,
+    /// An accumulator used to give launched applications unique sessions.
+    session_accumulator: u64,
+}
+
+impl State
+{
+    pub fn new(options: &Options) -> Self {
+        State {
+            name: options.name.clone(),
+            uuid: options.uuid,
+            apps: options.apps.clone(),
+            volume: options.volume.clone(),
+            running_app: None,
+            media_player: MediaPlayer::new(),
+            replay: options.fixture.clone().map(Replay::new),
+            session_accumulator: 1,
+        }
+    }
+
+    /// Moves the virtual clock of the media player forward.
+    ///
+    /// Returns the messages to broadcast to every sender.
+    pub fn advance(&mut self, seconds: f64) -> Vec<Message> {
+        let transport_id = match self.media_transport_id() {
+            Some(transport_id) => transport_id,
+            None => return Vec::new(),
+        };
+
+        self.media_player.advance(seconds).into_iter().map(|kind| Message {
+            source: transport_id.clone(),
+            destination: EndpointName::broadcast(),
+            namespace: ::back::protocol::namespace::media(),
+            kind: kind,
+        }).collect()
+    }
+
+    /// Gets the transport ID of the Default Media Receiver, if it is running.
+    fn media_transport_id(&self) -> Option<EndpointName> {
+        match self.running_app {
+            Some(ref app) if app.id == apps::default_media_receiver() => app.transport_id.clone(),
+            _ => None,
+        }
+    }
+
+    /// Generates a unique session ID.
+    fn create_session_id(&mut self) -> Uuid {
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes[8..].iter_mut().enumerate() {
+            *byte = (self.session_accumulator >> (56 - i * 8)) as u8;
+        }
+
+        self.session_accumulator += 1;
+        Uuid::from_bytes(&bytes).unwrap()
+    }
+}
+
+impl ReceiverHandler for State
+{
+    fn status(&self) -> Status {
+        Status {
+            volume: self.volume.clone(),
+            applications: self.running_app.iter().cloned().collect(),
+            is_active_input: None,
+            is_stand_by: None,
+            user_eq: None,
+        }
+    }
+
+    fn description(&self) -> DeviceDescription {
+        DeviceDescription {
+            device_id: self.uuid.simple().to_string(),
+            friendly_name: self.name.clone(),
+            model: Some("gcast emulator".to_owned()),
+            capabilities: None,
+            control_notifications: None,
+            icon_url: None,
+            receiver_metrics_id: None,
+        }
+    }
+
+    fn launch(&mut self, app_id: &ApplicationId, _: &LaunchOptions) -> Result<(), LaunchErrorReason> {
+        let app = match self.apps.iter().find(|app| app.app_id == *app_id) {
+            Some(app) => app.clone(),
+            None => return Err(LaunchErrorReason::NotFound),
+        };
+
+        let session_id = self.create_session_id();
+        self.media_player = MediaPlayer::new();
+
+        self.running_app = Some(Application {
+            id: app.app_id.clone(),
+            display_name: app.display_name.clone(),
+            is_idle_screen: false,
+            session_id: session_id,
+            status_text: app.display_name.clone(),
+            app_type: Some("WEB".to_owned()),
+            icon_url: None,
+            universal_app_id: Some(app.app_id.0.clone()),
+            launched_from_cloud: Some(false),
+            transport_id: Some(EndpointName(session_id.hyphenated().to_string())),
+            namespaces: app.namespaces.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn stop(&mut self, session_id: SessionId) -> Result<(), InvalidRequestReason> {
+        let is_running = self.running_app.as_ref().map_or(false, |app| app.session_id == session_id.0);
+        if !is_running { return Err(InvalidRequestReason::InvalidParams) };
+
+        self.running_app = None;
+        self.media_player = MediaPlayer::new();
+        Ok(())
+    }
+
+    fn set_volume(&mut self, level: Option<VolumeLevel>, muted: Option<bool>) -> Result<(), InvalidRequestReason> {
+        if let Some(level) = level { self.volume.level = level.clamp() };
+        if let Some(muted) = muted { self.volume.muted = muted };
+        Ok(())
+    }
+
+    fn app_availability(&self, app_id: &ApplicationId) -> AppAvailability {
+        if self.apps.iter().any(|app| app.app_id == *app_id) {
+            AppAvailability::Available
+        } else {
+            AppAvailability::Unavailable
+        }
+    }
+
+    /// Only the Default Media Receiver has a media player.
+    fn handle_media(&mut self, destination: &EndpointName, request: &MessageKind) -> Option<Response> {
+        if Some(destination) != self.media_transport_id().as_ref() {
+            return None;
+        }
+
+        self.media_player.handle(request)
+    }
+
+    /// Replays the fixture, if there is one.
+    fn handle(&mut self, message: &Message) -> Vec<Message> {
+        if let Some(ref mut replay) = self.replay {
+            if let Some(responses) = replay.handle(message) {
+                return responses;
+            }
+        }
+
+        receiver::dispatch(self, message)
+    }
+}
+
+impl EmulatedApp
+{
+    /// Creates an application that talks over the given namespaces.
+    pub fn new<S>(app_id: ApplicationId, display_name: S, namespaces: Vec<Namespace>) -> Self
+        where S: Into<String> {
+        EmulatedApp {
+            app_id: app_id,
+            display_name: display_name.into(),
+            namespaces: namespaces,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {VolumeLevel, SessionId};
+    use back::protocol::{Message, MessageKind, LaunchErrorReason};
+    use emulator::Options;
+    use receiver::ReceiverHandler;
+    use apps;
+
+    fn request(kind: MessageKind) -> Message {
+        Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: kind,
+        }
+    }
+
+    fn handle(state: &mut State, kind: MessageKind) -> MessageKind {
+        let mut responses = state.handle(&request(kind));
+        assert_eq!(responses.len(), 1);
+
+        let response = responses.remove(0);
+        assert!(response.destination == EndpointName("sender-0".to_owned()) || response.destination.is_broadcast());
+        response.kind
+    }
+
+    #[test]
+    fn launches_known_apps() {
+        let mut state = State::new(&Options::new());
+
+        match handle(&mut state, MessageKind::Launch { app_id: apps::youtube(), request_id: 1, options: Default::default() }) {
+            MessageKind::ReceiverStatus { status, .. } => assert_eq!(status.applications[0].id, apps::youtube()),
+            kind => panic!("expected a status, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn refuses_unknown_apps() {
+        let mut state = State::new(&Options::new());
+
+        assert_eq!(handle(&mut state, MessageKind::Launch { app_id: ApplicationId("Nope".to_owned()), request_id: 2, options: Default::default() }),
+                   MessageKind::LaunchError { reason: LaunchErrorReason::NotFound, request_id: 2 });
+    }
+
+    #[test]
+    fn stops_running_apps() {
+        let mut state = State::new(&Options::new());
+        handle(&mut state, MessageKind::Launch { app_id: apps::youtube(), request_id: 1, options: Default::default() });
+
+        let session_id = state.running_app.as_ref().unwrap().session_id;
+        handle(&mut state, MessageKind::Stop { session_id: SessionId(session_id), request_id: 2 });
+
+        assert_eq!(state.running_app, None);
+    }
+
+    #[test]
+    fn sessions_are_unique() {
+        let mut state = State::new(&Options::new());
+
+        assert!(state.create_session_id() != state.create_session_id());
+    }
+
+    #[test]
+    fn sets_the_volume() {
+        let mut state = State::new(&Options::new());
+
+        handle(&mut state, MessageKind::SetVolume { level: Some(VolumeLevel(1.5)), muted: Some(true), request_id: 1 });
+        assert_eq!(state.volume.level, VolumeLevel::max());
+        assert!(state.volume.muted);
+    }
+
+    #[test]
+    fn media_status_is_broadcast() {
+        let mut state = State::new(&Options::new());
+        handle(&mut state, MessageKind::Launch { app_id: apps::default_media_receiver(), request_id: 1, options: Default::default() });
+
+        let transport_id = state.running_app.as_ref().unwrap().transport_id.clone().unwrap();
+        let mut media = ::json::JsonValue::new_object();
+        media["contentId"] = "song.mp3".into();
+        media["duration"] = 30.0.into();
+
+        let responses = state.handle(&Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: transport_id.clone(),
+            namespace: ::back::protocol::namespace::media(),
+            kind: MessageKind::MediaLoad { media: media, autoplay: None, current_time: None, request_id: 2 },
+        });
+
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].destination.is_broadcast());
+        assert_eq!(responses[0].source, transport_id);
+
+        assert_eq!(state.advance(10.0), Vec::new());
+        assert_eq!(state.advance(30.0).len(), 1);
+    }
+
+    #[test]
+    fn answers_pings() {
+        let mut state = State::new(&Options::new());
+        let ping = Message { namespace: ::back::protocol::namespace::heartbeat(), ..request(MessageKind::Ping) };
+
+        assert_eq!(state.handle(&ping)[0].kind, MessageKind::Pong);
+    }
+
+    #[test]
+    fn launching_broadcasts_the_status() {
+        let mut state = State::new(&Options::new());
+        let responses = state.handle(&request(MessageKind::Launch { app_id: apps::youtube(), request_id: 1, options: Default::default() }));
+
+        assert!(responses[0].destination.is_broadcast());
+    }
+}
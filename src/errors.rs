@@ -10,4 +10,75 @@ error_chain! {
         ::std::io::Error, Io;
         ::back::protocol::Error, Protocol;
     }
+
+    errors {
+        InvalidChannelMessage(reason: String) {
+            description("message could not be converted for a typed channel")
+            display("invalid channel message: {}", reason)
+        }
+
+        FixedVolume {
+            description("the volume of the device is fixed and cannot be changed")
+            display("the volume of the device is fixed and cannot be changed")
+        }
+
+        ConnectionClosed {
+            description("the connection was closed")
+            display("the connection was closed")
+        }
+
+        PacketTooLarge(size: usize) {
+            description("a packet was larger than the maximum packet size")
+            display("a packet of {} bytes was larger than the maximum packet size", size)
+        }
+
+        TruncatedPacket(expected: usize, received: usize) {
+            description("the connection was closed in the middle of a packet")
+            display("the connection was closed after {} of {} bytes of a packet", received, expected)
+        }
+
+        Tls(reason: String) {
+            description("the TLS library failed")
+            display("the TLS library failed: {}", reason)
+        }
+
+        TlsHandshake(reason: String) {
+            description("the TLS handshake failed")
+            display("the TLS handshake failed: {}", reason)
+        }
+
+        InvalidFixture(line: usize, reason: String) {
+            description("a fixture could not be read")
+            display("invalid fixture on line {}: {}", line, reason)
+        }
+
+        InvalidRecording(line: usize, reason: String) {
+            description("a recording could not be read")
+            display("invalid recording on line {}: {}", line, reason)
+        }
+
+        NoMediaSession {
+            description("no media is being played")
+            display("no media is being played, call Device::update_media_status first")
+        }
+
+        NotLive {
+            description("the media is not a live stream")
+            display("the media is not a live stream")
+        }
+
+        StatusUnknown {
+            description("the status of the receiver is not yet known")
+            display("the status of the receiver is not yet known, call Device::update_status first")
+        }
+    }
+}
+
+// The openssl dependency is optional, so its errors can't be a foreign link.
+#[cfg(feature = "openssl")]
+impl From<::openssl::error::ErrorStack> for Error
+{
+    fn from(e: ::openssl::error::ErrorStack) -> Self {
+        ErrorKind::Tls(e.to_string()).into()
+    }
 }
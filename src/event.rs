@@ -1,9 +1,71 @@
 //! Events that occur on Cast devices.
 
+use {ApplicationId, AppAvailability};
+use back::protocol::{Message, Payload, Namespace, EndpointName, RequestId, RequestError, Member};
+
+use std::collections::HashMap;
+
 /// An event that occurred on a device.
 #[derive(Clone, Debug)]
 pub enum Event
 {
     /// The status of the receiver was updated.
     StatusUpdated,
+    /// The receiver described itself in response to `Device::device_info`.
+    DeviceInfoUpdated,
+    /// The status of the Cast audio group was updated.
+    GroupUpdated,
+    /// A speaker joined the Cast audio group.
+    MemberJoined(Member),
+    /// The volume or name of a speaker in the Cast audio group changed.
+    MemberUpdated(Member),
+    /// A speaker left the Cast audio group.
+    MemberLeft {
+        device_id: String,
+    },
+    /// The status of the media being played was updated.
+    MediaStatusUpdated,
+    /// A fade started with `Device::fade_volume` reached its target level.
+    VolumeFadeFinished,
+    /// The TV switched to or away from the Cast device's input.
+    ActiveInputChanged {
+        /// Whether the Cast device is now the active input.
+        is_active_input: bool,
+    },
+    /// The TV was turned on or went into standby.
+    StandbyChanged {
+        /// Whether the TV is now in standby.
+        is_stand_by: bool,
+    },
+    /// The receiver told us which applications it is able to run.
+    AppAvailability {
+        /// The ID of the `Device::app_availability` request.
+        request_id: RequestId,
+        availability: HashMap<ApplicationId, AppAvailability>,
+    },
+    /// The receiver rejected a request.
+    RequestFailed {
+        /// The ID of the request, as returned from methods such as `Device::launch`.
+        request_id: RequestId,
+        error: RequestError,
+    },
+    /// A message was received on a registered namespace.
+    NamespaceMessage {
+        namespace: Namespace,
+        /// The endpoint that sent the message.
+        source: EndpointName,
+        payload: Payload,
+    },
+    /// A message was received that could not be decoded.
+    ///
+    /// The message is skipped, and the messages after it are still handled.
+    InvalidMessage {
+        /// Why the message could not be decoded, naming the offending field
+        /// if there is one.
+        reason: String,
+    },
+    /// A message was received that is not handled by the `Device`.
+    ///
+    /// This includes message types that are unknown to this crate.
+    MessageReceived(Message),
 }
@@ -17,19 +17,64 @@
 //! connection to the Cast device and it abstracts over the protocol, making it
 //! easy to do things.
 //!
+//! Devices can also be talked to over any other non-blocking stream with
+//! `Device::connect_over`, such as an in-memory `back::net::Pipe`.
+//!
+//! TLS is provided by openssl by default. With the `rustls` feature, and
+//! without the default features, rustls is used instead. See the
+//! `back::net::tls` module for details.
+//!
 //! # Applications
 //!
 //! A list of valid application identifers can be found inside the `apps` module.
 //!
 //! You can launch and manage apps using these identifiers.
+//!
+//! # Custom namespaces
+//!
+//! Custom receiver applications can be talked to over their own namespaces
+//! using `Device::register_namespace` and `Device::send_json`.
+//!
+//! With the `channel` feature enabled, the `channel` module provides
+//! a serde-backed wrapper for this.
+//!
+//! # Receivers
+//!
+//! The `receiver` module has what is needed to act as a Cast device
+//! rather than talk to one. Senders connect to a `receiver::Server`, which
+//! passes their requests to a `receiver::ReceiverHandler`. Receivers need
+//! the `openssl` feature, as do the proxy and the emulator.
+//!
+//! # Proxying
+//!
+//! The `proxy` module, and the `gcast-proxy` binary built on it, sit
+//! between a sender and a device and log every message that passes.
+//!
+//! # Recording
+//!
+//! Everything a `Device` sends and receives can be recorded with
+//! `Device::record`. The `recording` module, and the `gcast-replay`
+//! binary built on it, play a recording back through a `Device` without
+//! a network, which reproduces the events it gave.
+//!
+//! # Testing
+//!
+//! The `emulator` module runs a local receiver that `Device` can
+//! connect to, for tests that can't rely on a real Cast device. It can
+//! also advertise itself over mDNS, so that discovery can be tested too.
 
 #![recursion_limit = "1024"]
 
 pub use self::errors::{Error, ErrorKind};
 pub use self::discovery::DeviceInfo;
 pub use self::device::Device;
-pub use self::back::protocol::{ApplicationId, SessionId, Status, Volume, VolumeLevel};
+pub use self::back::protocol::{ApplicationId, AppAvailability, RequestId, SessionId,
+                               Status, Volume, VolumeLevel, Namespace, EndpointName, Payload,
+                               LaunchOptions, AppType, Credentials, RequestError,
+                               DeviceDescription, MediaStatus, MediaSessionId, MediaVolume,
+                               PlayerState, LiveSeekableRange, SeekPosition, ResumeState};
 pub use self::event::Event;
+pub use self::media::MediaSession;
 
 pub mod discovery;
 pub mod errors;
@@ -37,12 +82,35 @@ pub mod back;
 pub mod device;
 pub mod event;
 pub mod apps;
+pub mod volume;
+pub mod media;
+#[cfg(feature = "openssl")]
+pub mod receiver;
+#[cfg(feature = "openssl")]
+pub mod emulator;
+#[cfg(feature = "openssl")]
+pub mod proxy;
+pub mod recording;
+#[cfg(feature = "channel")]
+pub mod channel;
 
 extern crate mdns;
 extern crate mio;
+extern crate net2;
 extern crate byteorder;
+extern crate protobuf;
 extern crate uuid;
 #[macro_use]
 extern crate error_chain;
+#[cfg(feature = "openssl")]
 extern crate openssl;
+#[cfg(feature = "rustls")]
+extern crate rustls;
+#[cfg(feature = "rustls")]
+extern crate webpki;
 extern crate libc;
+pub extern crate json;
+#[cfg(feature = "channel")]
+extern crate serde;
+#[cfg(feature = "channel")]
+extern crate serde_json;
@@ -0,0 +1,177 @@
+//! Tracking of media sessions between status updates.
+
+use back::protocol::{MediaStatus, PlayerState};
+
+use std::time::{Duration, Instant};
+
+/// A media session, as last reported by the receiver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaSession
+{
+    /// The last status received for the session.
+    pub status: MediaStatus,
+    /// The time the status was received.
+    pub received_at: Instant,
+}
+
+impl MediaSession
+{
+    /// Creates a session from a status that was received at a point in time.
+    pub fn new(status: MediaStatus, received_at: Instant) -> Self {
+        MediaSession { status: status, received_at: received_at }
+    }
+
+    /// Estimates the position in the media at a point in time, in seconds.
+    ///
+    /// Receivers only send `MEDIA_STATUS` when something changes, so
+    /// the position is extrapolated from the last status while the media
+    /// is playing. The result never goes past the duration of the media,
+    /// or outside the seekable range of a live stream.
+    pub fn estimated_position(&self, now: Instant) -> f64 {
+        let elapsed = self.elapsed(now);
+
+        let position = if self.status.player_state == PlayerState::Playing {
+            self.status.current_time + elapsed * self.status.playback_rate as f64
+        } else {
+            self.status.current_time
+        };
+
+        let (start, end) = self.seekable_range(elapsed);
+        let position = match end { Some(end) => position.min(end), None => position };
+        position.max(start)
+    }
+
+    /// Gets the bounds the position must stay within, `elapsed` seconds
+    /// after the status was received.
+    fn seekable_range(&self, elapsed: f64) -> (f64, Option<f64>) {
+        match self.status.live_seekable_range {
+            Some(range) => {
+                // The live edge keeps moving until the stream is over.
+                let live_elapsed = if range.is_live_done == Some(true) { 0.0 } else { elapsed };
+                let start_elapsed = if range.is_moving_window == Some(true) { live_elapsed } else { 0.0 };
+
+                (range.start.map_or(0.0, |start| start + start_elapsed),
+                 range.end.map(|end| end + live_elapsed))
+            },
+            None => (0.0, self.status.duration()),
+        }
+    }
+
+    fn elapsed(&self, now: Instant) -> f64 {
+        if now <= self.received_at { return 0.0 };
+        as_secs_f64(now - self.received_at)
+    }
+}
+
+fn as_secs_f64(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use back::protocol::{MediaStatus, MediaVolume, PlayerState, LiveSeekableRange};
+    use std::time::{Duration, Instant};
+    use json;
+
+    fn status(player_state: PlayerState, current_time: f64) -> MediaStatus {
+        MediaStatus {
+            media_session_id: 1,
+            player_state: player_state,
+            idle_reason: None,
+            current_time: current_time,
+            playback_rate: 1.0,
+            volume: MediaVolume { level: None, muted: None },
+            supported_media_commands: 0,
+            media: None,
+            live_seekable_range: None,
+        }
+    }
+
+    #[test]
+    fn playing_media_moves_forward() {
+        let start = Instant::now();
+        let session = MediaSession::new(status(PlayerState::Playing, 10.0), start);
+
+        assert_eq!(session.estimated_position(start), 10.0);
+        assert_eq!(session.estimated_position(start + Duration::from_secs(5)), 15.0);
+    }
+
+    #[test]
+    fn paused_media_stays_put() {
+        let start = Instant::now();
+        let session = MediaSession::new(status(PlayerState::Paused, 10.0), start);
+
+        assert_eq!(session.estimated_position(start + Duration::from_secs(5)), 10.0);
+    }
+
+    #[test]
+    fn accounts_for_the_playback_rate() {
+        let start = Instant::now();
+        let mut status = status(PlayerState::Playing, 10.0);
+        status.playback_rate = 1.5;
+        let session = MediaSession::new(status, start);
+
+        assert_eq!(session.estimated_position(start + Duration::from_secs(10)), 25.0);
+    }
+
+    #[test]
+    fn stops_at_the_duration() {
+        let start = Instant::now();
+        let mut status = status(PlayerState::Playing, 55.0);
+        let mut media = json::JsonValue::new_object();
+        media["duration"] = 60.0.into();
+        status.media = Some(media);
+        let session = MediaSession::new(status, start);
+
+        assert_eq!(session.estimated_position(start + Duration::from_secs(10)), 60.0);
+    }
+
+    #[test]
+    fn stays_within_a_finished_live_range() {
+        let start = Instant::now();
+        let mut status = status(PlayerState::Playing, 95.0);
+        status.live_seekable_range = Some(LiveSeekableRange {
+            start: Some(0.0),
+            end: Some(100.0),
+            is_moving_window: Some(false),
+            is_live_done: Some(true),
+        });
+        let session = MediaSession::new(status, start);
+
+        assert_eq!(session.estimated_position(start + Duration::from_secs(10)), 100.0);
+    }
+
+    #[test]
+    fn follows_the_live_edge() {
+        let start = Instant::now();
+        let mut status = status(PlayerState::Playing, 100.0);
+        status.playback_rate = 2.0;
+        status.live_seekable_range = Some(LiveSeekableRange {
+            start: Some(40.0),
+            end: Some(100.0),
+            is_moving_window: Some(true),
+            is_live_done: Some(false),
+        });
+        let session = MediaSession::new(status, start);
+
+        // Playing faster than real time can't get ahead of the live edge.
+        assert_eq!(session.estimated_position(start + Duration::from_secs(10)), 110.0);
+    }
+
+    #[test]
+    fn paused_live_streams_fall_out_of_a_moving_window() {
+        let start = Instant::now();
+        let mut status = status(PlayerState::Paused, 45.0);
+        status.live_seekable_range = Some(LiveSeekableRange {
+            start: Some(40.0),
+            end: Some(100.0),
+            is_moving_window: Some(true),
+            is_live_done: None,
+        });
+        let session = MediaSession::new(status, start);
+
+        assert_eq!(session.estimated_position(start + Duration::from_secs(10)), 50.0);
+    }
+}
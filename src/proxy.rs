@@ -0,0 +1,394 @@
+//! A proxy that sits between a sender and a Cast device.
+//!
+//! The proxy accepts TLS connections from senders with a self-signed
+//! certificate, and opens its own TLS connection to the device for each
+//! of them. Packets are forwarded both ways, and every message is logged
+//! as a line of JSON:
+//!
+//! ```text
+//! {"direction":"request","time":0.25,"message":{"sourceId":"sender-0",...}}
+//! ```
+//!
+//! Messages from the sender are requests and messages from the device are
+//! responses, so a log can be replayed by the emulator as a
+//! `emulator::Fixture`.
+//!
+//! Messages can be changed or dropped on their way through with a
+//! `Rewrite`.
+
+use {Error, ErrorKind, Namespace};
+use back::net::transport;
+use back::protocol::Message;
+use recording::Direction;
+use receiver::certificate;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::thread;
+
+use json;
+use openssl::ssl;
+use openssl::x509::X509;
+
+/// How long the proxy waits for one side before checking the other.
+const POLL_INTERVAL_MILLIS: u64 = 10;
+
+/// Where the log is written.
+type Log = Arc<Mutex<Box<Write + Send>>>;
+
+/// Changes messages as they pass through the proxy.
+///
+/// Closures taking the direction and message can be used as rewrites.
+pub trait Rewrite: Send + Sync
+{
+    /// Changes a message, or drops it by returning `None`.
+    fn rewrite(&self, direction: Direction, message: Message) -> Option<Message>;
+}
+
+/// Options for starting a proxy.
+#[derive(Clone)]
+pub struct Options
+{
+    /// The address of the Cast device to forward to.
+    pub target: SocketAddr,
+    /// The namespaces to log, or every namespace if empty.
+    pub namespaces: Vec<Namespace>,
+    /// The namespaces not to log.
+    pub excluded_namespaces: Vec<Namespace>,
+    /// Changes messages as they pass through.
+    pub rewrite: Option<Arc<Rewrite>>,
+}
+
+/// A running proxy.
+///
+/// The proxy stops when it is dropped.
+pub struct Proxy
+{
+    address: SocketAddr,
+    stopping: Arc<AtomicBool>,
+    accept_thread: Option<thread::JoinHandle<()>>,
+}
+
+/// The state of a single sender's connection.
+struct Session
+{
+    options: Options,
+    log: Log,
+    started_at: Instant,
+}
+
+impl<F> Rewrite for F
+    where F: Fn(Direction, Message) -> Option<Message> + Send + Sync {
+    fn rewrite(&self, direction: Direction, message: Message) -> Option<Message> {
+        self(direction, message)
+    }
+}
+
+impl Options
+{
+    /// Creates options for forwarding everything to a device, unchanged.
+    pub fn new(target: SocketAddr) -> Self {
+        Options {
+            target: target,
+            namespaces: Vec::new(),
+            excluded_namespaces: Vec::new(),
+            rewrite: None,
+        }
+    }
+
+    /// Only logs messages on a namespace, and any others given this way.
+    pub fn namespace(mut self, namespace: Namespace) -> Self {
+        self.namespaces.push(namespace);
+        self
+    }
+
+    /// Does not log messages on a namespace.
+    pub fn exclude_namespace(mut self, namespace: Namespace) -> Self {
+        self.excluded_namespaces.push(namespace);
+        self
+    }
+
+    /// Changes messages as they pass through.
+    pub fn rewrite<R>(mut self, rewrite: R) -> Self where R: Rewrite + 'static {
+        self.rewrite = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Checks whether messages on a namespace should be logged.
+    pub fn is_logged(&self, namespace: &Namespace) -> bool {
+        (self.namespaces.is_empty() || self.namespaces.contains(namespace)) &&
+            !self.excluded_namespaces.contains(namespace)
+    }
+}
+
+impl Proxy
+{
+    /// Starts accepting senders on a background thread.
+    pub fn start<W>(listener: TcpListener, options: Options, log: W) -> Result<Self, Error>
+        where W: Write + Send + 'static {
+        let (key, certificate) = certificate::generate()?;
+        let acceptor = ssl::SslAcceptorBuilder::mozilla_intermediate(
+            ssl::SslMethod::tls(), &key, &certificate, Vec::<X509>::new())?.build();
+
+        let address = listener.local_addr()?;
+        let log: Box<Write + Send> = Box::new(log);
+        let log: Log = Arc::new(Mutex::new(log));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let started_at = Instant::now();
+
+        let accept_thread = {
+            let stopping = stopping.clone();
+            let acceptor = Arc::new(acceptor);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopping.load(Ordering::SeqCst) { break };
+
+                    if let Ok(stream) = stream {
+                        let session = Session {
+                            options: options.clone(),
+                            log: log.clone(),
+                            started_at: started_at,
+                        };
+                        let stopping = stopping.clone();
+                        let acceptor = acceptor.clone();
+
+                        thread::spawn(move || {
+                            if let Err(e) = session.run(&acceptor, stream, &stopping) {
+                                session.log_error(&e);
+                            }
+                        });
+                    }
+                }
+            })
+        };
+
+        Ok(Proxy {
+            address: address,
+            stopping: stopping,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Gets the address the proxy is listening on.
+    pub fn address(&self) -> SocketAddr { self.address }
+}
+
+impl Drop for Proxy
+{
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        // Wake up the accept loop so that it notices.
+        let _ = TcpStream::connect(self.address);
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+    }
+}
+
+impl Session
+{
+    /// Forwards packets between a sender and the device until either disconnects.
+    fn run(&self, acceptor: &ssl::SslAcceptor, stream: TcpStream, stopping: &AtomicBool) -> Result<(), Error> {
+        let mut sender = acceptor.accept(stream).map_err(|e| ErrorKind::TlsHandshake(e.to_string()))?;
+
+        let mut connector = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls())?;
+        // Cast devices have certificates that do not chain to a public root.
+        connector.set_verify(ssl::SSL_VERIFY_NONE);
+        let mut device = connector.build()
+            .danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(
+                TcpStream::connect(self.options.target)?)
+            .map_err(|e| ErrorKind::TlsHandshake(e.to_string()))?;
+
+        for stream in &[sender.get_ref(), device.get_ref()] {
+            stream.set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL_MILLIS)))?;
+        }
+
+        let mut sender_reader = transport::Reader::new();
+        let mut device_reader = transport::Reader::new();
+
+        while !stopping.load(Ordering::SeqCst) {
+            if self.forward(Direction::Request, &mut sender_reader, &mut sender, &mut device)? ||
+                self.forward(Direction::Response, &mut device_reader, &mut device, &mut sender)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forwards the packets that are available from one side to the other.
+    ///
+    /// Returns `true` once the side being read from has disconnected.
+    fn forward(&self,
+               direction: Direction,
+               reader: &mut transport::Reader,
+               from: &mut Read,
+               to: &mut Write) -> Result<bool, Error> {
+        let mut packets = Vec::new();
+        let end_of_stream = reader.read(from, &mut packets)?;
+
+        for packet in packets {
+            // Packets we cannot understand are still forwarded, so that
+            // the proxy never gets in the way.
+            let packet = match Message::from_raw_bytes(&packet) {
+                Ok(message) => match self.process(direction, message) {
+                    Some(message) => message.as_raw_bytes()?,
+                    None => continue,
+                },
+                Err(e) => {
+                    self.log_error(&Error::from(e));
+                    packet
+                },
+            };
+
+            transport::write_packet(to, &packet)?;
+        }
+
+        if end_of_stream { reader.finish()? };
+        Ok(end_of_stream)
+    }
+
+    /// Logs and rewrites a message.
+    fn process(&self, direction: Direction, message: Message) -> Option<Message> {
+        if self.options.is_logged(&message.namespace) {
+            let mut line = self.log_line();
+            line["direction"] = direction.as_str().into();
+            line["message"] = message.to_json();
+            self.write_log(line);
+        }
+
+        match self.options.rewrite {
+            Some(ref rewrite) => rewrite.rewrite(direction, message),
+            None => Some(message),
+        }
+    }
+
+    fn log_error(&self, error: &Error) {
+        let mut line = self.log_line();
+        line["error"] = error.to_string().into();
+        self.write_log(line);
+    }
+
+    /// Starts a line of the log.
+    fn log_line(&self) -> json::JsonValue {
+        let elapsed = self.started_at.elapsed();
+
+        let mut line = json::JsonValue::new_object();
+        line["time"] = (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0).into();
+        line
+    }
+
+    fn write_log(&self, line: json::JsonValue) {
+        let mut log = self.log.lock().unwrap();
+
+        // A log that cannot be written to should not stop the proxy.
+        let _ = writeln!(log, "{}", json::stringify(line)).and_then(|_| log.flush());
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use Namespace;
+    use back::net::transport;
+    use back::protocol::{Message, MessageKind, EndpointName};
+    use emulator::{self, Emulator};
+    use recording::Direction;
+
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    use openssl::ssl;
+
+    /// A log that can be read by the test while the proxy writes to it.
+    #[derive(Clone)]
+    struct SharedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedLog
+    {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> { self.0.lock().unwrap().write(buf) }
+        fn flush(&mut self) -> ::std::io::Result<()> { Ok(()) }
+    }
+
+    fn connect(proxy: &Proxy) -> ssl::SslStream<TcpStream> {
+        let mut connector = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls()).unwrap();
+        connector.set_verify(ssl::SSL_VERIFY_NONE);
+
+        let stream = TcpStream::connect(proxy.address()).unwrap();
+        connector.build().danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream).unwrap()
+    }
+
+    fn get_status() -> Message {
+        Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: MessageKind::GetStatus { request_id: 1 },
+        }
+    }
+
+    fn start(options: Options) -> (Proxy, SharedLog) {
+        let log = SharedLog(Arc::new(Mutex::new(Vec::new())));
+        let listener = TcpListener::bind((Ipv4Addr::new(127, 0, 0, 1), 0)).unwrap();
+
+        (Proxy::start(listener, options, log.clone()).unwrap(), log)
+    }
+
+    #[test]
+    fn forwards_and_logs_messages() {
+        let emulator = Emulator::start(emulator::Options::new()).unwrap();
+        let (proxy, log) = start(Options::new(emulator.address()));
+        let mut stream = connect(&proxy);
+
+        transport::write_packet(&mut stream, &get_status().as_raw_bytes().unwrap()).unwrap();
+        let response = Message::from_raw_bytes(&transport::read_packet(&mut stream).unwrap().unwrap()).unwrap();
+        assert_eq!(response.kind, MessageKind::ReceiverStatus { status: emulator.status(), request_id: 1 });
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        let fixture = emulator::Fixture::parse(&log).unwrap();
+
+        assert_eq!(fixture.entries[0].direction, Direction::Request);
+        assert_eq!(fixture.entries[0].message, get_status());
+        assert_eq!(fixture.entries[1].message, response);
+    }
+
+    #[test]
+    fn rewrites_messages() {
+        let emulator = Emulator::start(emulator::Options::new()).unwrap();
+        let options = Options::new(emulator.address()).rewrite(|direction: Direction, message: Message| {
+            match direction {
+                Direction::Request => Some(Message { kind: MessageKind::GetDeviceInfo { request_id: 9 }, ..message }),
+                Direction::Response => Some(message),
+            }
+        });
+        let (proxy, _) = start(options);
+        let mut stream = connect(&proxy);
+
+        transport::write_packet(&mut stream, &get_status().as_raw_bytes().unwrap()).unwrap();
+        let response = Message::from_raw_bytes(&transport::read_packet(&mut stream).unwrap().unwrap()).unwrap();
+
+        match response.kind {
+            MessageKind::DeviceInfo { request_id, .. } => assert_eq!(request_id, 9),
+            kind => panic!("unexpected message: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn namespaces_can_be_filtered() {
+        let options = Options::new("127.0.0.1:8009".parse().unwrap())
+            .exclude_namespace(::back::protocol::namespace::heartbeat());
+
+        assert!(options.is_logged(&::back::protocol::namespace::receiver()));
+        assert!(!options.is_logged(&::back::protocol::namespace::heartbeat()));
+
+        let options = options.namespace(Namespace("urn:x-cast:com.example".to_owned()));
+        assert!(!options.is_logged(&::back::protocol::namespace::receiver()));
+    }
+}
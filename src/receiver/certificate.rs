@@ -0,0 +1,49 @@
+//! Self-signed certificates for TLS listeners.
+
+use Error;
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MSB_MAYBE_ZERO};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::x509::{X509, X509Builder, X509NameBuilder};
+use openssl::x509::extension::BasicConstraints;
+
+/// The common name put on emulator certificates.
+const COMMON_NAME: &'static str = "gcast-emulator";
+/// How long generated certificates are valid for.
+const VALID_DAYS: u32 = 365;
+
+/// Generates a private key and a certificate signed by it.
+///
+/// Senders do not verify the certificate of Cast devices, so
+/// a self-signed certificate is enough.
+pub fn generate() -> Result<(PKey, X509), Error> {
+    let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_text("CN", COMMON_NAME)?;
+    let name = name.build();
+
+    let mut serial_number = BigNum::new()?;
+    serial_number.rand(128, MSB_MAYBE_ZERO, false)?;
+    let serial_number = serial_number.to_asn1_integer()?;
+
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(VALID_DAYS)?;
+
+    let mut certificate = X509Builder::new()?;
+    certificate.set_version(2)?;
+    certificate.set_serial_number(&serial_number)?;
+    certificate.set_subject_name(&name)?;
+    certificate.set_issuer_name(&name)?;
+    certificate.set_pubkey(&key)?;
+    certificate.set_not_before(&not_before)?;
+    certificate.set_not_after(&not_after)?;
+    // rustls refuses certificates without any extensions.
+    certificate.append_extension(BasicConstraints::new().build()?)?;
+    certificate.sign(&key, MessageDigest::sha256())?;
+
+    Ok((key, certificate.build()))
+}
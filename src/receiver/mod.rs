@@ -0,0 +1,330 @@
+//! Acting as a Cast receiver.
+//!
+//! A `Server` accepts TLS connections from senders and keeps track of
+//! their virtual connections. Requests are given to a `ReceiverHandler`,
+//! which is implemented by the receiver. Heartbeats are answered without
+//! bothering the handler.
+//!
+//! Requests that change the receiver, such as launching an application or
+//! changing the volume, are answered by broadcasting the new status to
+//! every connected sender, as real receivers do.
+//!
+//! ```ignore
+//! struct Speaker { volume: Volume }
+//!
+//! impl ReceiverHandler for Speaker {
+//!     fn status(&self) -> Status { ... }
+//!     fn description(&self) -> DeviceDescription { ... }
+//!
+//!     fn set_volume(&mut self, level: Option<VolumeLevel>, muted: Option<bool>)
+//!         -> Result<(), InvalidRequestReason> {
+//!         ...
+//!     }
+//! }
+//!
+//! let listener = TcpListener::bind(("0.0.0.0", discovery::DEFAULT_PORT))?;
+//! let server = Server::start(listener, Arc::new(Mutex::new(Speaker::new())))?;
+//! ```
+
+pub use self::server::{Server, Interceptor, Outcome};
+
+pub mod certificate;
+pub mod server;
+
+use {ApplicationId, AppAvailability, LaunchOptions, SessionId, Status, VolumeLevel,
+     DeviceDescription, EndpointName};
+use back::protocol::{Message, MessageKind, InvalidRequestReason, LaunchErrorReason};
+
+/// A message to send back for a request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Response
+{
+    /// Send the message to the sender that made the request.
+    Reply(MessageKind),
+    /// Send the message to every connected sender.
+    Broadcast(MessageKind),
+}
+
+/// The behaviour of a receiver.
+///
+/// Only the status and description of the receiver must be given. The
+/// other requests are refused unless their methods are implemented.
+pub trait ReceiverHandler: Send
+{
+    /// Gets the current status of the receiver.
+    fn status(&self) -> Status;
+
+    /// Describes the receiver, in answer to `GET_DEVICE_INFO`.
+    fn description(&self) -> DeviceDescription;
+
+    /// Launches an application.
+    fn launch(&mut self, _app_id: &ApplicationId, _options: &LaunchOptions) -> Result<(), LaunchErrorReason> {
+        Err(LaunchErrorReason::NotFound)
+    }
+
+    /// Stops a running application.
+    fn stop(&mut self, _session_id: SessionId) -> Result<(), InvalidRequestReason> {
+        Err(InvalidRequestReason::InvalidParams)
+    }
+
+    /// Changes the volume of the receiver.
+    fn set_volume(&mut self, _level: Option<VolumeLevel>, _muted: Option<bool>) -> Result<(), InvalidRequestReason> {
+        Err(InvalidRequestReason::InvalidCommand)
+    }
+
+    /// Checks whether an application could be launched.
+    fn app_availability(&self, _app_id: &ApplicationId) -> AppAvailability {
+        AppAvailability::Unavailable
+    }
+
+    /// Handles a request on the media namespace.
+    ///
+    /// The destination is the transport ID of the application that the
+    /// request was sent to.
+    fn handle_media(&mut self, _destination: &EndpointName, _request: &MessageKind) -> Option<Response> {
+        None
+    }
+
+    /// Handles a message on any other namespace, such as the custom
+    /// namespace of an application.
+    fn handle_message(&mut self, _message: &Message) -> Vec<Message> {
+        Vec::new()
+    }
+
+    /// Called when a sender opens a virtual connection.
+    fn connected(&mut self, _source: &EndpointName, _destination: &EndpointName) { }
+
+    /// Called when a virtual connection is closed, or when the sender
+    /// that opened it disconnects.
+    fn disconnected(&mut self, _source: &EndpointName, _destination: &EndpointName) { }
+
+    /// Handles a message from a sender, returning the messages to send back.
+    ///
+    /// Messages with a broadcast destination are sent to every sender. This
+    /// calls `dispatch`, and can be overridden to see every message first.
+    fn handle(&mut self, message: &Message) -> Vec<Message> where Self: Sized {
+        dispatch(self, message)
+    }
+}
+
+/// Gives a message to the method of a handler that deals with it.
+pub fn dispatch<H>(handler: &mut H, message: &Message) -> Vec<Message>
+    where H: ReceiverHandler {
+    let responses = if message.namespace == ::back::protocol::namespace::heartbeat() {
+        match message.kind {
+            MessageKind::Ping => vec![Response::Reply(MessageKind::Pong)],
+            _ => Vec::new(),
+        }
+    } else if message.namespace == ::back::protocol::namespace::connection() {
+        // Virtual connections are tracked by the server.
+        Vec::new()
+    } else if message.namespace == ::back::protocol::namespace::receiver() {
+        match dispatch_receiver(handler, &message.kind) {
+            Some(response) => vec![response],
+            None => return handler.handle_message(message),
+        }
+    } else if message.namespace == ::back::protocol::namespace::media() {
+        handler.handle_media(&message.destination, &message.kind).into_iter().collect()
+    } else {
+        return handler.handle_message(message);
+    };
+
+    responses.into_iter().map(|response| {
+        let (destination, kind) = match response {
+            Response::Reply(kind) => (message.source.clone(), kind),
+            Response::Broadcast(kind) => (EndpointName::broadcast(), kind),
+        };
+
+        Message {
+            source: message.destination.clone(),
+            destination: destination,
+            namespace: message.namespace.clone(),
+            kind: kind,
+        }
+    }).collect()
+}
+
+/// Handles a request on the receiver namespace.
+///
+/// Returns `None` for messages that are left to `handle_message`.
+fn dispatch_receiver<H>(handler: &mut H, request: &MessageKind) -> Option<Response>
+    where H: ReceiverHandler {
+    let status = |handler: &H, request_id| Response::Broadcast(MessageKind::ReceiverStatus {
+        status: handler.status(),
+        request_id: request_id,
+    });
+    let invalid = |reason, request_id| Response::Reply(MessageKind::InvalidRequest {
+        reason: reason,
+        request_id: request_id,
+    });
+
+    let response = match *request {
+        MessageKind::GetStatus { request_id } => Response::Reply(MessageKind::ReceiverStatus {
+            status: handler.status(),
+            request_id: request_id,
+        }),
+        MessageKind::Launch { ref app_id, request_id, ref options } => {
+            match handler.launch(app_id, options) {
+                Ok(()) => status(&*handler, request_id),
+                Err(reason) => Response::Reply(MessageKind::LaunchError { reason: reason, request_id: request_id }),
+            }
+        },
+        MessageKind::Stop { session_id, request_id } => {
+            match handler.stop(session_id) {
+                Ok(()) => status(&*handler, request_id),
+                Err(reason) => invalid(reason, request_id),
+            }
+        },
+        MessageKind::SetVolume { level, muted, request_id } => {
+            match handler.set_volume(level, muted) {
+                Ok(()) => status(&*handler, request_id),
+                Err(reason) => invalid(reason, request_id),
+            }
+        },
+        MessageKind::GetAppAvailability { ref app_ids, request_id } => Response::Reply(MessageKind::AppAvailability {
+            availability: app_ids.iter().map(|app_id| (app_id.clone(), handler.app_availability(app_id))).collect(),
+            request_id: request_id,
+        }),
+        MessageKind::GetDeviceInfo { request_id } => Response::Reply(MessageKind::DeviceInfo {
+            description: handler.description(),
+            request_id: request_id,
+        }),
+        MessageKind::Unknown { ref payload, .. } => invalid(InvalidRequestReason::InvalidCommand,
+                                                             payload["requestId"].as_i64().unwrap_or(0)),
+        _ => return None,
+    };
+
+    Some(response)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {ApplicationId, AppAvailability, Status, Volume, VolumeLevel, DeviceDescription, EndpointName};
+    use back::protocol::{Message, MessageKind, InvalidRequestReason};
+
+    /// A receiver that can only change its volume.
+    struct Speaker
+    {
+        volume: Volume,
+    }
+
+    impl ReceiverHandler for Speaker
+    {
+        fn status(&self) -> Status {
+            Status {
+                volume: self.volume.clone(),
+                applications: Vec::new(),
+                is_active_input: None,
+                is_stand_by: None,
+                user_eq: None,
+            }
+        }
+
+        fn description(&self) -> DeviceDescription {
+            DeviceDescription {
+                device_id: "speaker".to_owned(),
+                friendly_name: "Speaker".to_owned(),
+                model: None,
+                capabilities: None,
+                control_notifications: None,
+                icon_url: None,
+                receiver_metrics_id: None,
+            }
+        }
+
+        fn set_volume(&mut self, level: Option<VolumeLevel>, _: Option<bool>) -> Result<(), InvalidRequestReason> {
+            if let Some(level) = level { self.volume.level = level };
+            Ok(())
+        }
+    }
+
+    fn speaker() -> Speaker {
+        Speaker {
+            volume: Volume {
+                control_type: "attenuation".to_owned(),
+                level: VolumeLevel(0.5),
+                muted: false,
+                step_interval: VolumeLevel(0.05),
+            },
+        }
+    }
+
+    fn request(namespace: ::Namespace, kind: MessageKind) -> Message {
+        Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: namespace,
+            kind: kind,
+        }
+    }
+
+    #[test]
+    fn answers_pings() {
+        let responses = speaker().handle(&request(::back::protocol::namespace::heartbeat(), MessageKind::Ping));
+
+        assert_eq!(responses, vec![Message {
+            source: EndpointName("receiver-0".to_owned()),
+            destination: EndpointName("sender-0".to_owned()),
+            namespace: ::back::protocol::namespace::heartbeat(),
+            kind: MessageKind::Pong,
+        }]);
+    }
+
+    #[test]
+    fn changes_are_broadcast() {
+        let mut speaker = speaker();
+        let responses = speaker.handle(&request(::back::protocol::namespace::receiver(),
+                                                MessageKind::SetVolume { level: Some(VolumeLevel(0.2)), muted: None, request_id: 5 }));
+
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].destination.is_broadcast());
+        assert_eq!(responses[0].kind, MessageKind::ReceiverStatus { status: speaker.status(), request_id: 5 });
+    }
+
+    #[test]
+    fn refusals_name_the_request() {
+        let responses = speaker().handle(&request(::back::protocol::namespace::receiver(), MessageKind::Stop {
+            session_id: SessionId(::uuid::Uuid::nil()),
+            request_id: 6,
+        }));
+
+        assert_eq!(responses[0].destination, EndpointName("sender-0".to_owned()));
+        assert_eq!(responses[0].kind, MessageKind::InvalidRequest {
+            reason: InvalidRequestReason::InvalidParams,
+            request_id: 6,
+        });
+    }
+
+    #[test]
+    fn unimplemented_requests_are_refused() {
+        let responses = speaker().handle(&request(::back::protocol::namespace::receiver(), MessageKind::Launch {
+            app_id: ApplicationId("CC1AD845".to_owned()),
+            request_id: 3,
+            options: Default::default(),
+        }));
+
+        assert_eq!(responses[0].kind, MessageKind::LaunchError {
+            reason: ::back::protocol::LaunchErrorReason::NotFound,
+            request_id: 3,
+        });
+    }
+
+    #[test]
+    fn answers_app_availability() {
+        let app_id = ApplicationId("CC1AD845".to_owned());
+        let responses = speaker().handle(&request(::back::protocol::namespace::receiver(), MessageKind::GetAppAvailability {
+            app_ids: vec![app_id.clone()],
+            request_id: 4,
+        }));
+
+        match responses[0].kind {
+            MessageKind::AppAvailability { ref availability, request_id } => {
+                assert_eq!(availability[&app_id], AppAvailability::Unavailable);
+                assert_eq!(request_id, 4);
+            },
+            ref kind => panic!("unexpected message: {:?}", kind),
+        }
+    }
+}
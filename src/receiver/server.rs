@@ -0,0 +1,428 @@
+//! Accepting connections from senders.
+
+use {Error, ErrorKind};
+use back::net::transport;
+use back::protocol::{Message, MessageKind, EndpointName};
+use receiver::{certificate, ReceiverHandler};
+
+use std::collections::HashSet;
+use std::io::prelude::*;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{io, thread};
+
+use openssl::ssl;
+use openssl::x509::X509;
+
+/// How long a connection waits for a message before checking for broadcasts.
+const POLL_INTERVAL_MILLIS: u64 = 20;
+
+/// How long a closed connection waits for the sender to close its side.
+const CLOSE_TIMEOUT_MILLIS: u64 = 1000;
+
+/// The queues of messages waiting to be written to each connected sender.
+type Senders = Arc<Mutex<Vec<mpsc::Sender<Message>>>>;
+
+/// What a connection should do after an interceptor has run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome
+{
+    /// Keep talking to the sender.
+    Continue,
+    /// Close the connection.
+    Close,
+}
+
+/// Changes how a server talks to senders.
+///
+/// This is mostly useful for making a receiver misbehave in tests.
+pub trait Interceptor: Send + Sync
+{
+    /// Called before each read from a sender, with the stream to the sender.
+    fn before_read(&self, _stream: &mut Write) -> Result<Outcome, Error> { Ok(Outcome::Continue) }
+
+    /// Checks whether a message from a sender should be handled at all.
+    fn accepts(&self, _message: &Message) -> bool { true }
+
+    /// Called before each response is sent.
+    fn before_response(&self, _response: &Message) { }
+}
+
+/// An interceptor that changes nothing.
+struct PassThrough;
+
+impl Interceptor for PassThrough { }
+
+/// A running receiver.
+///
+/// Each sender is served on its own thread. The server stops when it is
+/// dropped.
+pub struct Server
+{
+    address: SocketAddr,
+    senders: Senders,
+    stopping: Arc<AtomicBool>,
+    accept_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Server
+{
+    /// Starts serving senders that connect to a listener.
+    ///
+    /// A self-signed certificate is generated for the TLS connections.
+    pub fn start<H>(listener: TcpListener, handler: Arc<Mutex<H>>) -> Result<Self, Error>
+        where H: ReceiverHandler + 'static {
+        Server::start_intercepted(listener, handler, Arc::new(PassThrough))
+    }
+
+    /// Starts serving senders, with an interceptor that can change how
+    /// the server behaves.
+    pub fn start_intercepted<H>(listener: TcpListener,
+                                handler: Arc<Mutex<H>>,
+                                interceptor: Arc<Interceptor>) -> Result<Self, Error>
+        where H: ReceiverHandler + 'static {
+        let (key, certificate) = certificate::generate()?;
+        let acceptor = ssl::SslAcceptorBuilder::mozilla_intermediate(
+            ssl::SslMethod::tls(), &key, &certificate, Vec::<X509>::new())?.build();
+
+        let address = listener.local_addr()?;
+        let senders = Arc::new(Mutex::new(Vec::new()));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let accept_thread = {
+            let senders = senders.clone();
+            let stopping = stopping.clone();
+            let acceptor = Arc::new(acceptor);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopping.load(Ordering::SeqCst) { break };
+
+                    if let Ok(stream) = stream {
+                        let handler = handler.clone();
+                        let interceptor = interceptor.clone();
+                        let senders = senders.clone();
+                        let stopping = stopping.clone();
+                        let acceptor = acceptor.clone();
+
+                        // A sender disconnecting is not an error for the receiver.
+                        thread::spawn(move || {
+                            let _ = serve(&acceptor, stream, &*handler, &*interceptor, &senders, &stopping);
+                        });
+                    }
+                }
+            })
+        };
+
+        Ok(Server {
+            address: address,
+            senders: senders,
+            stopping: stopping,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Gets the address the server is listening on.
+    pub fn address(&self) -> SocketAddr { self.address }
+
+    /// Sends a message to every connected sender.
+    ///
+    /// This is used for changes that were not asked for by a sender, such
+    /// as media finishing.
+    pub fn broadcast(&self, message: Message) {
+        broadcast(&self.senders, message);
+    }
+}
+
+impl Drop for Server
+{
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        // Wake up the accept loop so that it notices.
+        let _ = TcpStream::connect(self.address);
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+    }
+}
+
+/// Talks to a single sender until it disconnects.
+fn serve<H>(acceptor: &ssl::SslAcceptor,
+            stream: TcpStream,
+            handler: &Mutex<H>,
+            interceptor: &Interceptor,
+            senders: &Senders,
+            stopping: &AtomicBool) -> Result<(), Error>
+    where H: ReceiverHandler {
+    let mut stream = acceptor.accept(stream).map_err(|e| ErrorKind::TlsHandshake(e.to_string()))?;
+    // Wake up regularly to write out messages broadcast by other connections.
+    stream.get_ref().set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL_MILLIS)))?;
+
+    let (sender, queued_messages) = mpsc::channel();
+    senders.lock().unwrap().push(sender);
+
+    let mut virtual_connections = HashSet::new();
+    let result = serve_messages(&mut stream, handler, interceptor, senders, stopping,
+                                &queued_messages, &mut virtual_connections);
+
+    // Connections that were never closed end with the sender.
+    let mut handler = handler.lock().unwrap();
+    for (source, destination) in virtual_connections {
+        handler.disconnected(&source, &destination);
+    }
+
+    result
+}
+
+fn serve_messages<H>(stream: &mut ssl::SslStream<TcpStream>,
+                     handler: &Mutex<H>,
+                     interceptor: &Interceptor,
+                     senders: &Senders,
+                     stopping: &AtomicBool,
+                     queued_messages: &mpsc::Receiver<Message>,
+                     virtual_connections: &mut HashSet<(EndpointName, EndpointName)>) -> Result<(), Error>
+    where H: ReceiverHandler {
+    let mut reader = transport::Reader::new();
+
+    while !stopping.load(Ordering::SeqCst) {
+        if interceptor.before_read(stream)? == Outcome::Close { return close(stream.get_mut()) };
+
+        let mut packets = Vec::new();
+        let end_of_stream = reader.read(stream, &mut packets)?;
+
+        for packet in packets {
+            // Messages that can't be decoded are dropped, rather than
+            // ending the connection, so one bad message doesn't cut the
+            // sender off.
+            let message = match Message::from_raw_bytes(&packet) {
+                Ok(message) => message,
+                Err(..) => continue,
+            };
+            if !interceptor.accepts(&message) { continue };
+
+            let responses = {
+                let mut handler = handler.lock().unwrap();
+                track_virtual_connection(&mut *handler, virtual_connections, &message);
+                handler.handle(&message)
+            };
+
+            for response in responses {
+                interceptor.before_response(&response);
+
+                if response.destination.is_broadcast() {
+                    broadcast(senders, response);
+                } else {
+                    transport::write_packet(stream, &response.as_raw_bytes()?)?;
+                }
+            }
+        }
+
+        for message in queued_messages.try_iter() {
+            transport::write_packet(stream, &message.as_raw_bytes()?)?;
+        }
+
+        if end_of_stream { return reader.finish() };
+    }
+
+    Ok(())
+}
+
+/// Closes a connection, waiting for the sender to notice.
+///
+/// Dropping a socket that has unread data resets the connection, and the
+/// reset can overtake whatever was written last. Instead the socket is shut
+/// down for writing, and what the sender sends afterwards is thrown away.
+fn close(stream: &mut TcpStream) -> Result<(), Error> {
+    stream.shutdown(Shutdown::Write)?;
+
+    let deadline = Instant::now() + Duration::from_millis(CLOSE_TIMEOUT_MILLIS);
+    let mut buffer = [0; 1024];
+
+    while Instant::now() < deadline {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(..) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                          e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Remembers the virtual connections a sender opens and closes.
+fn track_virtual_connection<H>(handler: &mut H,
+                               virtual_connections: &mut HashSet<(EndpointName, EndpointName)>,
+                               message: &Message)
+    where H: ReceiverHandler {
+    if message.namespace != ::back::protocol::namespace::connection() { return };
+
+    let connection = (message.source.clone(), message.destination.clone());
+
+    match message.kind {
+        MessageKind::Connect => {
+            if virtual_connections.insert(connection) {
+                handler.connected(&message.source, &message.destination);
+            }
+        },
+        MessageKind::Close => {
+            if virtual_connections.remove(&connection) {
+                handler.disconnected(&message.source, &message.destination);
+            }
+        },
+        _ => (),
+    }
+}
+
+/// Queues a message to be written to every connected sender.
+fn broadcast(senders: &Senders, message: Message) {
+    // Senders that have disconnected are forgotten.
+    senders.lock().unwrap().retain(|sender| sender.send(message.clone()).is_ok());
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {Status, Volume, VolumeLevel, DeviceDescription, Namespace};
+    use back::protocol::namespace;
+
+    use std::net::Ipv4Addr;
+    use std::time::Instant;
+
+    /// A receiver that remembers the virtual connections opened to it.
+    struct Tracker
+    {
+        calls: Vec<(&'static str, EndpointName, EndpointName)>,
+    }
+
+    impl ReceiverHandler for Tracker
+    {
+        fn status(&self) -> Status {
+            Status {
+                volume: Volume {
+                    control_type: "attenuation".to_owned(),
+                    level: VolumeLevel(0.5),
+                    muted: false,
+                    step_interval: VolumeLevel(0.05),
+                },
+                applications: Vec::new(),
+                is_active_input: None,
+                is_stand_by: None,
+                user_eq: None,
+            }
+        }
+
+        fn description(&self) -> DeviceDescription {
+            DeviceDescription {
+                device_id: "tracker".to_owned(),
+                friendly_name: "Tracker".to_owned(),
+                model: None,
+                capabilities: None,
+                control_notifications: None,
+                icon_url: None,
+                receiver_metrics_id: None,
+            }
+        }
+
+        fn connected(&mut self, source: &EndpointName, destination: &EndpointName) {
+            self.calls.push(("connected", source.clone(), destination.clone()));
+        }
+
+        fn disconnected(&mut self, source: &EndpointName, destination: &EndpointName) {
+            self.calls.push(("disconnected", source.clone(), destination.clone()));
+        }
+    }
+
+    fn request(namespace: Namespace, kind: MessageKind) -> Message {
+        Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: namespace,
+            kind: kind,
+        }
+    }
+
+    fn start() -> (Server, Arc<Mutex<Tracker>>) {
+        let tracker = Arc::new(Mutex::new(Tracker { calls: Vec::new() }));
+        let listener = TcpListener::bind((Ipv4Addr::new(127, 0, 0, 1), 0)).unwrap();
+
+        (Server::start(listener, tracker.clone()).unwrap(), tracker)
+    }
+
+    fn connect(server: &Server) -> ssl::SslStream<TcpStream> {
+        let mut connector = ssl::SslConnectorBuilder::new(ssl::SslMethod::tls()).unwrap();
+        connector.set_verify(ssl::SSL_VERIFY_NONE);
+        let connector = connector.build();
+
+        let stream = TcpStream::connect(server.address()).unwrap();
+        connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream).unwrap()
+    }
+
+    /// Asks for the status, so that every message sent before has been handled
+    /// once the answer arrives.
+    fn get_status(stream: &mut ssl::SslStream<TcpStream>) -> Message {
+        transport::write_packet(stream, &request(namespace::receiver(), MessageKind::GetStatus { request_id: 1 }).as_raw_bytes().unwrap()).unwrap();
+
+        let packet = transport::read_packet(stream).unwrap().unwrap();
+        Message::from_raw_bytes(&packet).unwrap()
+    }
+
+    #[test]
+    fn virtual_connections_are_tracked() {
+        let mut tracker = Tracker { calls: Vec::new() };
+        let mut virtual_connections = HashSet::new();
+        let connect = request(namespace::connection(), MessageKind::Connect);
+        let close = request(namespace::connection(), MessageKind::Close);
+
+        track_virtual_connection(&mut tracker, &mut virtual_connections, &connect);
+        track_virtual_connection(&mut tracker, &mut virtual_connections, &connect);
+        assert_eq!(virtual_connections.len(), 1);
+
+        track_virtual_connection(&mut tracker, &mut virtual_connections, &close);
+        track_virtual_connection(&mut tracker, &mut virtual_connections, &close);
+        assert!(virtual_connections.is_empty());
+
+        let sender = EndpointName("sender-0".to_owned());
+        let receiver = EndpointName("receiver-0".to_owned());
+        assert_eq!(tracker.calls, vec![("connected", sender.clone(), receiver.clone()),
+                                       ("disconnected", sender, receiver)]);
+    }
+
+    #[test]
+    fn senders_that_drop_are_disconnected() {
+        let (server, tracker) = start();
+        let mut stream = connect(&server);
+
+        transport::write_packet(&mut stream, &request(namespace::connection(), MessageKind::Connect).as_raw_bytes().unwrap()).unwrap();
+        get_status(&mut stream);
+        drop(stream);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while tracker.lock().unwrap().calls.len() < 2 {
+            assert!(Instant::now() < deadline, "the sender was never disconnected");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let calls = tracker.lock().unwrap().calls.clone();
+        assert_eq!(calls.iter().map(|call| call.0).collect::<Vec<_>>(), vec!["connected", "disconnected"]);
+    }
+
+    #[test]
+    fn bad_messages_are_skipped() {
+        let (server, _) = start();
+        let mut stream = connect(&server);
+
+        transport::write_packet(&mut stream, &[0xff, 0x00, 0x13]).unwrap();
+
+        match get_status(&mut stream).kind {
+            MessageKind::ReceiverStatus { .. } => (),
+            kind => panic!("unexpected message: {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Recording the traffic of a `Device`, and playing it back without a network.
+//!
+//! A recording is a file of JSON lines, in the same form as the log of a
+//! `proxy::Proxy`. Each line has the `time` in seconds since recording
+//! started, a `direction` of `request` for packets the device was sent or
+//! `response` for packets it received, and the `message`:
+//!
+//! ```text
+//! {"time":0.5,"direction":"response","message":{"sourceId":"receiver-0","destinationId":"sender-0","namespace":"urn:x-cast:com.google.cast.receiver","payloadUtf8":{"type":"RECEIVER_STATUS","status":{...}}}}
+//! ```
+//!
+//! Packets are recorded before they are decoded, so that a message we fail
+//! to understand can be replayed to find out why. A payload that is not
+//! valid JSON is kept as a string in `payloadText`, and a packet that is not
+//! a valid wire message is kept as an array of bytes in `packet` instead of
+//! a `message`.
+//!
+//! The first packet a device sends is a request to connect, so a recording
+//! of messages that could be decoded is also an `emulator::Fixture`.
+//!
+//! A `Replayer` gives the packets that were received to a `Device` that is
+//! not connected to anything, which raises the same events as it did when
+//! the recording was made.
+//!
+//! ```ignore
+//! device.record(File::create("session.jsonl")?);
+//!
+//! // Later, without a network:
+//! let mut replayer = Replayer::new(device_info, Recording::open("session.jsonl")?);
+//!
+//! while let Some(events) = replayer.step() {
+//!     println!("{:?}", events?);
+//! }
+//! ```
+
+use {Device, DeviceInfo, Event, Error, ErrorKind};
+use back::protocol;
+use back::net::transport::RawPacket;
+use back::protocol::{wire, Payload};
+use back::protocol::field::Field;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+
+use json;
+use protobuf;
+
+/// Which way a recorded message went.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction
+{
+    /// A message from a sender to a receiver.
+    Request,
+    /// A message from a receiver to a sender.
+    Response,
+}
+
+/// A recorded packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry
+{
+    /// The line of the recording the packet was read from.
+    pub line: usize,
+    /// The number of seconds between the start of the recording and the packet.
+    pub time: f64,
+    pub direction: Direction,
+    pub packet: RawPacket,
+}
+
+/// A recorded session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording
+{
+    pub entries: Vec<Entry>,
+}
+
+/// Writes packets to a recording as they are sent and received.
+pub struct Recorder
+{
+    log: Box<Write + Send>,
+    started_at: Instant,
+}
+
+/// Plays a recording back through a device.
+pub struct Replayer
+{
+    device: Device,
+    entries: ::std::vec::IntoIter<Entry>,
+    current: Option<Entry>,
+}
+
+impl Direction
+{
+    /// Gets the direction with a name used in recordings.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "request" => Some(Direction::Request),
+            "response" => Some(Direction::Response),
+            _ => None,
+        }
+    }
+
+    /// Gets the name used for the direction in recordings.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Direction::Request => "request",
+            Direction::Response => "response",
+        }
+    }
+}
+
+impl Recording
+{
+    /// Reads a recording from a file.
+    pub fn open<P>(path: P) -> Result<Self, Error> where P: AsRef<Path> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+
+        Recording::parse(&text)
+    }
+
+    /// Reads a recording from JSON lines.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() { continue };
+
+            let line_number = index + 1;
+            let invalid = |reason: String| ErrorKind::InvalidRecording(line_number, reason);
+
+            let data = json::parse(line).map_err(|e| invalid(e.to_string()))?;
+            let direction = data["direction"].as_str().and_then(Direction::from_name)
+                .ok_or_else(|| invalid("expected a direction of 'request' or 'response'".to_owned()))?;
+            let time = Field::root(&data, "").get("time").as_f64().map_err(|e| invalid(e.to_string()))?;
+            let packet = packet_from_json(&data).map_err(|e| invalid(e.to_string()))?;
+
+            entries.push(Entry {
+                line: line_number,
+                time: time,
+                direction: direction,
+                packet: packet,
+            });
+        }
+
+        Ok(Recording { entries: entries })
+    }
+}
+
+impl Recorder
+{
+    /// Creates a recorder that writes JSON lines to a log.
+    pub fn new<W>(log: W) -> Self where W: Write + Send + 'static {
+        Recorder {
+            log: Box::new(log),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Writes a packet to the recording.
+    pub fn record(&mut self, direction: Direction, packet: &[u8]) {
+        let elapsed = self.started_at.elapsed();
+
+        let mut line = packet_to_json(packet);
+        line["time"] = (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0).into();
+        line["direction"] = direction.as_str().into();
+
+        // A recording that cannot be written to should not stop the device.
+        let _ = writeln!(self.log, "{}", json::stringify(line)).and_then(|_| self.log.flush());
+    }
+}
+
+impl Replayer
+{
+    /// Creates a replayer for a recording of a device.
+    pub fn new(info: DeviceInfo, recording: Recording) -> Self {
+        Replayer {
+            device: Device::replaying(info),
+            entries: recording.entries.into_iter(),
+            current: None,
+        }
+    }
+
+    /// Gives the next received packet to the device, returning the events
+    /// that it raised.
+    ///
+    /// Packets that the device sent are skipped, as the replayed device
+    /// sends its own. Returns `None` once the recording has run out.
+    pub fn step(&mut self) -> Option<Result<Vec<Event>, Error>> {
+        while let Some(entry) = self.entries.next() {
+            if entry.direction != Direction::Response { continue };
+
+            let result = self.device.replay_packet(entry.packet.clone());
+            self.current = Some(entry);
+
+            return Some(result.map(|_| self.device.events().into_iter().collect()));
+        }
+
+        None
+    }
+
+    /// Gets the entry that was played last.
+    pub fn current(&self) -> Option<&Entry> { self.current.as_ref() }
+
+    /// Gets the device the recording is being played through.
+    pub fn device(&self) -> &Device { &self.device }
+}
+
+/// Describes a packet in the form written by `Message::to_json`.
+///
+/// The payload is kept as it was sent, rather than as we decode it.
+fn packet_to_json(packet: &[u8]) -> json::JsonValue {
+    let mut data = json::JsonValue::new_object();
+
+    let message: wire::CastMessage = match protobuf::parse_from_bytes(packet) {
+        Ok(message) => message,
+        Err(..) => {
+            data["packet"] = packet.to_owned().into();
+            return data;
+        },
+    };
+
+    let mut json_message = json::JsonValue::new_object();
+    json_message["sourceId"] = message.get_source_id().into();
+    json_message["destinationId"] = message.get_destination_id().into();
+    json_message["namespace"] = message.get_namespace().into();
+
+    match Payload::from_wire_message(&message) {
+        Ok(payload) => payload.write_json(&mut json_message),
+        Err(..) => json_message["payloadText"] = message.get_payload_utf8().into(),
+    }
+
+    data["message"] = json_message;
+    data
+}
+
+/// Rebuilds a packet from the JSON written by `packet_to_json`.
+fn packet_from_json(data: &json::JsonValue) -> Result<RawPacket, Error> {
+    let root = Field::root(data, "");
+
+    let packet = root.get("packet");
+    if packet.is_present() {
+        return Ok(packet.as_bytes()?);
+    }
+
+    let json_message = root.get("message");
+    let mut message = wire::CastMessage::new();
+    message.set_protocol_version(protocol::message::PROTOCOL_VERSION);
+    message.set_source_id(json_message.get("sourceId").as_str()?.to_owned());
+    message.set_destination_id(json_message.get("destinationId").as_str()?.to_owned());
+    message.set_namespace(json_message.get("namespace").as_str()?.to_owned());
+
+    let text = json_message.get("payloadText");
+    if text.is_present() {
+        message.set_payload_type(wire::CastMessage_PayloadType::STRING);
+        message.set_payload_utf8(text.as_str()?.to_owned());
+    } else {
+        Payload::from_json(&json_message)?.write_to(&mut message);
+    }
+
+    let bytes = protobuf::Message::write_to_bytes(&message).map_err(protocol::Error::from)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {DeviceInfo, Event, EndpointName};
+    use back::protocol::{wire, Message, MessageKind, RequestError, InvalidRequestReason};
+
+    use std::net::Ipv4Addr;
+
+    use json;
+    use protobuf;
+    use uuid::Uuid;
+
+    fn invalid_request() -> Message {
+        Message {
+            source: EndpointName("receiver-0".to_owned()),
+            destination: EndpointName("sender-0".to_owned()),
+            namespace: ::back::protocol::namespace::receiver(),
+            kind: MessageKind::InvalidRequest {
+                reason: InvalidRequestReason::InvalidCommand,
+                request_id: 3,
+            },
+        }
+    }
+
+    fn line(direction: Direction, packet: &[u8]) -> String {
+        let mut data = packet_to_json(packet);
+        data["time"] = 0.5.into();
+        data["direction"] = direction.as_str().into();
+        json::stringify(data)
+    }
+
+    #[test]
+    fn packets_survive_a_round_trip() {
+        let packet = invalid_request().as_raw_bytes().unwrap();
+        let recording = Recording::parse(&line(Direction::Response, &packet)).unwrap();
+
+        assert_eq!(recording.entries, vec![Entry {
+            line: 1,
+            time: 0.5,
+            direction: Direction::Response,
+            packet: packet,
+        }]);
+    }
+
+    #[test]
+    fn undecodable_packets_are_kept() {
+        let mut message = invalid_request().as_wire_message();
+        message.set_payload_utf8("{\"type\":".to_owned());
+        let invalid_payload = protobuf::Message::write_to_bytes(&message).unwrap();
+        let invalid_message = vec![0xff, 0x00, 0x13];
+
+        let text = [line(Direction::Response, &invalid_payload),
+                    line(Direction::Response, &invalid_message)].join("\n");
+        let recording = Recording::parse(&text).unwrap();
+
+        assert_eq!(recording.entries[0].packet, invalid_payload);
+        assert_eq!(recording.entries[1].packet, invalid_message);
+    }
+
+    #[test]
+    fn replays_events() {
+        let ping = Message {
+            source: EndpointName("sender-0".to_owned()),
+            destination: EndpointName("receiver-0".to_owned()),
+            namespace: ::back::protocol::namespace::heartbeat(),
+            kind: MessageKind::Ping,
+        };
+        let text = [line(Direction::Request, &ping.as_raw_bytes().unwrap()),
+                    line(Direction::Response, &invalid_request().as_raw_bytes().unwrap())].join("\n");
+
+        let info = DeviceInfo { ip_addr: Ipv4Addr::new(127, 0, 0, 1), port: 8009, uuid: Uuid::nil(), name: None };
+        let mut replayer = Replayer::new(info, Recording::parse(&text).unwrap());
+
+        let events = replayer.step().unwrap().unwrap();
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::RequestFailed { request_id: 3, error: RequestError::InvalidRequest(InvalidRequestReason::InvalidCommand) } => (),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(replayer.current().map(|entry| entry.line), Some(2));
+        assert!(replayer.step().is_none());
+    }
+
+    #[test]
+    fn decoding_errors_are_raised_in_order() {
+        let mut message: wire::CastMessage = invalid_request().as_wire_message();
+        message.set_payload_utf8("{\"type\":".to_owned());
+        let text = [line(Direction::Response, &protobuf::Message::write_to_bytes(&message).unwrap()),
+                    line(Direction::Response, &invalid_request().as_raw_bytes().unwrap())].join("\n");
+
+        let info = DeviceInfo { ip_addr: Ipv4Addr::new(127, 0, 0, 1), port: 8009, uuid: Uuid::nil(), name: None };
+        let mut replayer = Replayer::new(info, Recording::parse(&text).unwrap());
+
+        let events = replayer.step().unwrap().unwrap();
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Event::InvalidMessage { .. } => (),
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(replayer.step().unwrap().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Helpers for changing the volume gradually.
+
+use VolumeLevel;
+
+use std::time::{Duration, Instant};
+
+/// The smallest change in level that is worth sending to the device.
+const MINIMUM_FADE_STEP: f32 = 0.01;
+
+/// A gradual change of volume from one level to another.
+///
+/// The fade does not send anything by itself. It is driven by
+/// `Device::update`, which asks it for the level at the current time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fade
+{
+    /// The level at the start of the fade.
+    pub from: VolumeLevel,
+    /// The level at the end of the fade.
+    pub to: VolumeLevel,
+    /// The time the fade started.
+    pub started_at: Instant,
+    /// How long the fade lasts.
+    pub duration: Duration,
+    /// The last level that was sent to the device.
+    last_level: VolumeLevel,
+}
+
+impl Fade
+{
+    /// Creates a new fade starting at a point in time.
+    pub fn new(from: VolumeLevel,
+               to: VolumeLevel,
+               started_at: Instant,
+               duration: Duration) -> Self {
+        let from = from.clamp();
+
+        Fade {
+            from: from,
+            to: to.clamp(),
+            started_at: started_at,
+            duration: duration,
+            last_level: from,
+        }
+    }
+
+    /// Gets the level the volume should be at, at a point in time.
+    pub fn level_at(&self, now: Instant) -> VolumeLevel {
+        if now <= self.started_at { return self.from };
+
+        let elapsed = as_secs_f32(now - self.started_at);
+        let total = as_secs_f32(self.duration);
+
+        if total <= 0.0 || elapsed >= total { return self.to };
+
+        let progress = elapsed / total;
+        VolumeLevel(self.from.0 + (self.to.0 - self.from.0) * progress)
+    }
+
+    /// Checks whether the fade has reached its target level.
+    pub fn is_finished(&self, now: Instant) -> bool {
+        now >= self.started_at + self.duration
+    }
+
+    /// Gets the level that should be sent to the device, if any.
+    ///
+    /// Levels that differ only slightly from the last one sent are skipped so
+    /// that the device isn't flooded with messages. The final level is always
+    /// returned once.
+    pub fn next_level(&mut self, now: Instant) -> Option<VolumeLevel> {
+        let level = self.level_at(now);
+        let difference = (level.0 - self.last_level.0).abs();
+
+        let should_send = if self.is_finished(now) {
+            level != self.last_level
+        } else {
+            difference >= MINIMUM_FADE_STEP
+        };
+
+        if should_send {
+            self.last_level = level;
+            Some(level)
+        } else {
+            None
+        }
+    }
+}
+
+fn as_secs_f32(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use VolumeLevel;
+    use std::time::{Duration, Instant};
+
+    fn fade(from: f32, to: f32, seconds: u64) -> (Fade, Instant) {
+        let start = Instant::now();
+        (Fade::new(VolumeLevel(from), VolumeLevel(to), start, Duration::from_secs(seconds)), start)
+    }
+
+    #[test]
+    fn fades_linearly() {
+        let (fade, start) = fade(0.0, 1.0, 30);
+
+        assert_eq!(fade.level_at(start), VolumeLevel(0.0));
+        assert!((fade.level_at(start + Duration::from_secs(15)).0 - 0.5).abs() < 0.0001);
+        assert_eq!(fade.level_at(start + Duration::from_secs(30)), VolumeLevel(1.0));
+        assert_eq!(fade.level_at(start + Duration::from_secs(60)), VolumeLevel(1.0));
+    }
+
+    #[test]
+    fn fades_down() {
+        let (fade, start) = fade(0.8, 0.2, 10);
+        assert!((fade.level_at(start + Duration::from_secs(5)).0 - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn clamps_the_levels() {
+        let (fade, start) = fade(-0.5, 1.5, 10);
+
+        assert_eq!(fade.from, VolumeLevel::min());
+        assert_eq!(fade.level_at(start + Duration::from_secs(10)), VolumeLevel::max());
+    }
+
+    #[test]
+    fn skips_tiny_steps() {
+        let (mut fade, start) = fade(0.0, 1.0, 100);
+
+        assert_eq!(fade.next_level(start + Duration::from_millis(100)), None);
+        assert!(fade.next_level(start + Duration::from_secs(1)).is_some());
+        assert_eq!(fade.next_level(start + Duration::from_millis(1100)), None);
+    }
+
+    #[test]
+    fn always_sends_the_final_level_once() {
+        let (mut fade, start) = fade(0.5, 0.505, 1);
+        let end = start + Duration::from_secs(1);
+
+        assert!(fade.is_finished(end));
+        assert_eq!(fade.next_level(end), Some(VolumeLevel(0.505)));
+        assert_eq!(fade.next_level(end), None);
+    }
+
+    #[test]
+    fn finishes_immediately_without_a_duration() {
+        let (fade, start) = fade(0.0, 1.0, 0);
+
+        assert!(fade.is_finished(start));
+        assert_eq!(fade.level_at(start + Duration::from_millis(1)), VolumeLevel(1.0));
+    }
+}